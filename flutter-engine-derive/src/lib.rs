@@ -0,0 +1,46 @@
+//! Procedural macros of flutter-engine. They are re-exported by `flutter-engine` when its
+//! `derive` feature is enabled, the generated code refers to `::flutter_engine`.
+
+extern crate proc_macro;
+
+mod method_channel;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, AttributeArgs, ItemTrait};
+
+/// Turn a trait into the typed interface of a method channel.
+///
+/// ```ignore
+/// #[method_channel(name = "flutter-rs/window", codec = "JSON_CODEC")]
+/// pub trait WindowHandler {
+///     fn maximize(&mut self);
+///
+///     fn is_maximized(&mut self) -> bool;
+///
+///     #[method(name = "set_pos")]
+///     fn set_pos(&mut self, pos: PositionParams);
+/// }
+/// ```
+///
+/// The codec is the path of a `MethodCodec` static and defaults to the standard codec. Methods are
+/// called by the camel case version of their name, `#[method(name = "...")]` overrides it.
+/// Arguments have to implement `Serialize` and `DeserializeOwned`. A method with a single argument
+/// gets the arguments of the call, for more arguments they are looked up by their camel case name
+/// in the map of arguments. Methods return nothing, a value or a `Result<T, MethodError<D>>`.
+///
+/// Next to the trait `WindowHandlerChannel` is generated. It has the `NAME` and `codec()` of the
+/// channel, `dispatch` to answer a call with a handler, and `channel` to create a `MethodChannel`
+/// answering calls with a handler. Calls with invalid arguments are answered with an
+/// `invalid-args` error, unknown methods with not implemented.
+///
+/// For channels whose methods are implemented in Dart, `#[method_channel(name = "...", client)]`
+/// also generates `WindowHandlerClient`, which invokes the methods on a registered channel. Its
+/// methods take `&self` and return a `ResponseFuture` of the method's result.
+#[proc_macro_attribute]
+pub fn method_channel(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let item = parse_macro_input!(input as ItemTrait);
+    method_channel::expand(args, item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
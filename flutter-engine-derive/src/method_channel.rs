@@ -0,0 +1,495 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_quote, Attribute, AttributeArgs, Error, FnArg, GenericArgument, Ident, ItemTrait, Lit,
+    Meta, NestedMeta, Pat, Path, PathArguments, Result, ReturnType, TraitItem, TraitItemMethod,
+    Type,
+};
+
+struct ChannelArgs {
+    name: String,
+    codec: Path,
+    /// Whether to generate a client for methods implemented in Dart
+    client: bool,
+}
+
+enum Output {
+    Unit,
+    Value(Type),
+    /// The success type of a `Result<T, MethodError<D>>`
+    Result(Type),
+}
+
+struct Method {
+    ident: Ident,
+    /// The name Dart calls the method by
+    name: String,
+    args: Vec<(Ident, Type)>,
+    output: Output,
+    docs: Vec<Attribute>,
+}
+
+pub(crate) fn expand(args: AttributeArgs, mut item: ItemTrait) -> Result<TokenStream> {
+    let args = parse_channel_args(args)?;
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "method channels can't be generic",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    for trait_item in &mut item.items {
+        if let TraitItem::Method(method) = trait_item {
+            methods.push(parse_method(method)?);
+        }
+    }
+
+    let channel = expand_channel(&args, &item, &methods);
+    let client = if args.client {
+        expand_client(&args, &item, &methods)
+    } else {
+        quote!()
+    };
+    Ok(quote! {
+        #item
+        #channel
+        #client
+    })
+}
+
+fn parse_channel_args(args: AttributeArgs) -> Result<ChannelArgs> {
+    let mut name = None;
+    let mut codec = None;
+    let mut client = false;
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::Path(arg)) if arg.is_ident("client") => client = true,
+            NestedMeta::Meta(Meta::NameValue(arg)) if arg.path.is_ident("name") => {
+                name = Some(lit_str(&arg.lit)?.value());
+            }
+            NestedMeta::Meta(Meta::NameValue(arg)) if arg.path.is_ident("codec") => {
+                codec = Some(lit_str(&arg.lit)?.parse()?);
+            }
+            arg => {
+                return Err(Error::new(
+                    arg.span(),
+                    "unknown argument, expected `name`, `codec` or `client`",
+                ))
+            }
+        }
+    }
+    Ok(ChannelArgs {
+        name: name.ok_or_else(|| Error::new(Span::call_site(), "missing the channel `name`"))?,
+        codec: codec.unwrap_or_else(|| parse_quote!(::flutter_engine::codec::STANDARD_CODEC)),
+        client,
+    })
+}
+
+fn lit_str(lit: &Lit) -> Result<&syn::LitStr> {
+    match lit {
+        Lit::Str(lit) => Ok(lit),
+        lit => Err(Error::new(lit.span(), "expected a string")),
+    }
+}
+
+/// Parses the method and removes its `#[method]` attribute, which is only meaningful to the macro.
+fn parse_method(method: &mut TraitItemMethod) -> Result<Method> {
+    let sig = &method.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "methods of a method channel can't be generic",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {}
+        _ => {
+            return Err(Error::new(
+                sig.span(),
+                "methods of a method channel need a `&self` or `&mut self` receiver",
+            ))
+        }
+    }
+    let mut args = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => args.push((pat.ident.clone(), (*arg.ty).clone())),
+                pat => return Err(Error::new(pat.span(), "expected an argument name")),
+            },
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "unexpected receiver"))
+            }
+        }
+    }
+
+    let mut name = None;
+    let mut attrs = Vec::new();
+    for attr in method.attrs.drain(..) {
+        if attr.path.is_ident("method") {
+            name = Some(parse_method_name(&attr)?);
+        } else {
+            attrs.push(attr);
+        }
+    }
+    method.attrs = attrs;
+
+    let sig = &method.sig;
+    Ok(Method {
+        ident: sig.ident.clone(),
+        name: name.unwrap_or_else(|| camel_case(&sig.ident.to_string())),
+        args,
+        output: parse_output(&sig.output)?,
+        docs: method
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("doc"))
+            .cloned()
+            .collect(),
+    })
+}
+
+fn parse_method_name(attr: &Attribute) -> Result<String> {
+    if let Meta::List(list) = attr.parse_meta()? {
+        if let Some(NestedMeta::Meta(Meta::NameValue(arg))) = list.nested.first() {
+            if list.nested.len() == 1 && arg.path.is_ident("name") {
+                return Ok(lit_str(&arg.lit)?.value());
+            }
+        }
+    }
+    Err(Error::new(
+        attr.span(),
+        "expected `#[method(name = \"...\")]`",
+    ))
+}
+
+fn parse_output(output: &ReturnType) -> Result<Output> {
+    let ty = match output {
+        ReturnType::Default => return Ok(Output::Unit),
+        ReturnType::Type(_, ty) => ty,
+    };
+    let path = match &**ty {
+        Type::Tuple(tuple) if tuple.elems.is_empty() => return Ok(Output::Unit),
+        Type::Path(path) => path,
+        ty => return Ok(Output::Value(ty.clone())),
+    };
+    let segment = path.path.segments.last().unwrap();
+    if segment.ident != "Result" {
+        return Ok(Output::Value((**ty).clone()));
+    }
+    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+        let mut args = args.args.iter();
+        if let (Some(GenericArgument::Type(ok)), Some(GenericArgument::Type(err)), None) =
+            (args.next(), args.next(), args.next())
+        {
+            if is_method_error(err) {
+                return Ok(Output::Result(ok.clone()));
+            }
+            return Err(Error::new(
+                err.span(),
+                "the error of a method has to be a `MethodError<D>`",
+            ));
+        }
+    }
+    Err(Error::new(
+        segment.span(),
+        "methods have to return a `Result<T, MethodError<D>>`",
+    ))
+}
+
+fn is_method_error(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => {
+                segment.ident == "MethodError"
+                    && matches!(segment.arguments, PathArguments::AngleBracketed(_))
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn expand_channel(args: &ChannelArgs, item: &ItemTrait, methods: &[Method]) -> TokenStream {
+    let vis = &item.vis;
+    let trait_ident = &item.ident;
+    let channel_ident = format_ident!("{}Channel", trait_ident);
+    let name = &args.name;
+    let codec = &args.codec;
+    let doc = format!("The method channel `{}` of `{}`.", name, trait_ident);
+
+    let arms = methods.iter().map(|method| {
+        let ident = &method.ident;
+        let name = &method.name;
+        let decode = match method.args.as_slice() {
+            [] => quote!(),
+            [(arg, ty)] => {
+                let decode = quote!(call.try_args());
+                decode_arg(name, arg, ty, decode)
+            }
+            args => args
+                .iter()
+                .map(|(arg, ty)| {
+                    let key = camel_case(&arg.to_string());
+                    decode_arg(name, arg, ty, quote!(call.try_arg(#key)))
+                })
+                .collect(),
+        };
+        let arg_idents = method.args.iter().map(|(arg, _)| arg);
+        let result = quote!(handler.#ident(#(#arg_idents),*));
+        let respond = match method.output {
+            Output::Unit => quote! {
+                #result;
+                call.success_empty()
+            },
+            Output::Value(_) => quote!(call.success(#result)),
+            Output::Result(_) => quote!(call.respond(#result)),
+        };
+        quote! {
+            #name => {
+                #decode
+                #respond
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #vis struct #channel_ident;
+
+        impl #channel_ident {
+            pub const NAME: &'static str = #name;
+
+            pub fn codec() -> &'static dyn ::flutter_engine::codec::MethodCodec {
+                &#codec
+            }
+
+            /// Answer `call` with the method of `handler` it invokes.
+            pub fn dispatch<H>(handler: &mut H, call: ::flutter_engine::channel::MethodCall)
+            where
+                H: #trait_ident + ?Sized,
+            {
+                match call.method().as_str() {
+                    #(#arms)*
+                    _ => call.not_implemented(),
+                }
+            }
+
+            /// Create a channel whose calls are answered by `handler`.
+            pub fn channel<H>(handler: H) -> ::flutter_engine::channel::MethodChannel
+            where
+                H: #trait_ident + 'static,
+            {
+                struct Dispatcher<H>(H);
+
+                impl<H> ::flutter_engine::channel::MethodCallHandler for Dispatcher<H>
+                where
+                    H: #trait_ident,
+                {
+                    fn on_method_call(&mut self, call: ::flutter_engine::channel::MethodCall) {
+                        #channel_ident::dispatch(&mut self.0, call)
+                    }
+                }
+
+                ::flutter_engine::channel::MethodChannel::new(
+                    Self::NAME,
+                    Dispatcher(handler),
+                    Self::codec(),
+                )
+            }
+        }
+    }
+}
+
+fn decode_arg(method: &str, arg: &Ident, ty: &Type, decode: TokenStream) -> TokenStream {
+    quote! {
+        let #arg: #ty = match #decode {
+            Ok(arg) => arg,
+            Err(err) => {
+                return call.error(
+                    "invalid-args",
+                    format!("Invalid arguments for {}: {}", #method, err),
+                    ::flutter_engine::codec::Value::Null,
+                )
+            }
+        };
+    }
+}
+
+fn expand_client(args: &ChannelArgs, item: &ItemTrait, methods: &[Method]) -> TokenStream {
+    let vis = &item.vis;
+    let trait_ident = &item.ident;
+    let client_ident = format_ident!("{}Client", trait_ident);
+    let doc = format!(
+        "Invokes the methods of `{}` implemented in Dart on the channel `{}`.",
+        trait_ident, args.name
+    );
+
+    let methods = methods.iter().map(|method| {
+        let ident = &method.ident;
+        let name = &method.name;
+        let docs = &method.docs;
+        let arg_idents = method.args.iter().map(|(arg, _)| arg);
+        let arg_types = method.args.iter().map(|(_, ty)| ty);
+        let value = match method.args.as_slice() {
+            [] => quote!(()),
+            [(arg, _)] => quote!(#arg),
+            args => {
+                let keys = args.iter().map(|(arg, _)| camel_case(&arg.to_string()));
+                let values = args.iter().map(|(arg, _)| arg);
+                quote! {{
+                    let mut args = ::std::collections::HashMap::new();
+                    #(
+                        args.insert(
+                            #keys.to_owned(),
+                            match ::flutter_engine::codec::value::to_value(#values) {
+                                Ok(value) => value,
+                                Err(err) => {
+                                    return ::flutter_engine::channel::ResponseFuture::ready(Err(
+                                        ::flutter_engine::error::InvokeError::SerializeError(err),
+                                    ))
+                                }
+                            },
+                        );
+                    )*
+                    ::flutter_engine::codec::Value::Map(args)
+                }}
+            }
+        };
+        let output = match &method.output {
+            Output::Unit => quote!(()),
+            Output::Value(ty) | Output::Result(ty) => quote!(#ty),
+        };
+        quote! {
+            #(#docs)*
+            pub fn #ident(
+                &self,
+                #(#arg_idents: #arg_types),*
+            ) -> ::flutter_engine::channel::ResponseFuture<#output> {
+                match self.channel.upgrade() {
+                    Some(channel) => channel.invoke_method_async(#name, #value),
+                    None => ::flutter_engine::channel::ResponseFuture::ready(Err(
+                        ::flutter_engine::error::EngineError::EngineDropped.into(),
+                    )),
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #vis struct #client_ident {
+            channel: ::std::sync::Weak<::flutter_engine::channel::MethodChannel>,
+        }
+
+        impl #client_ident {
+            /// `channel` is the channel registered with the name of the trait's channel.
+            pub fn new(channel: ::std::sync::Weak<::flutter_engine::channel::MethodChannel>) -> Self {
+                Self { channel }
+            }
+
+            #(#methods)*
+        }
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_').filter(|part| !part.is_empty());
+    let mut camel = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{camel_case, expand};
+    use proc_macro2::TokenStream;
+    use quote::quote;
+    use syn::parse::Parser;
+    use syn::punctuated::Punctuated;
+    use syn::{parse_quote, AttributeArgs, ItemTrait, NestedMeta, Token};
+
+    fn args(tokens: TokenStream) -> AttributeArgs {
+        let parser = Punctuated::<NestedMeta, Token![,]>::parse_terminated;
+        parser.parse2(tokens).unwrap().into_iter().collect()
+    }
+
+    fn expand_str(args: AttributeArgs, item: ItemTrait) -> String {
+        expand(args, item).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        let expanded = expand_str(
+            args(quote!(name = "flutter-rs/window")),
+            parse_quote! {
+                trait WindowHandler {
+                    fn set_pos(&mut self, x: f64, y: f64);
+                }
+            },
+        );
+        assert!(expanded.contains(r#"call . try_arg ("x")"#));
+        assert!(expanded.contains(r#"return call . error ("invalid-args""#));
+    }
+
+    #[test]
+    fn test_not_implemented() {
+        let expanded = expand_str(
+            args(quote!(name = "flutter-rs/window")),
+            parse_quote! {
+                trait WindowHandler {
+                    #[method(name = "close")]
+                    fn dismiss(&mut self);
+                }
+            },
+        );
+        assert!(expanded.contains(r#""close" =>"#));
+        assert!(expanded.contains("_ => call . not_implemented ()"));
+    }
+
+    #[test]
+    fn test_client() {
+        let item: ItemTrait = parse_quote! {
+            trait DialogHandler {
+                fn confirm(&mut self, title: String) -> bool;
+            }
+        };
+        let expanded = expand_str(args(quote!(name = "flutter-rs/dialog")), item.clone());
+        assert!(!expanded.contains("DialogHandlerClient"));
+        let expanded = expand_str(args(quote!(name = "flutter-rs/dialog", client)), item);
+        assert!(expanded.contains("struct DialogHandlerClient"));
+        assert!(expanded.contains(r#"channel . invoke_method_async ("confirm" , title)"#));
+    }
+
+    #[test]
+    fn test_result_error() {
+        let item: ItemTrait = parse_quote! {
+            trait WindowHandler {
+                fn is_maximized(&mut self) -> Result<bool, String>;
+            }
+        };
+        let err = expand(args(quote!(name = "flutter-rs/window")), item).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the error of a method has to be a `MethodError<D>`"
+        );
+    }
+
+    #[test]
+    fn test_camel_case() {
+        assert_eq!(camel_case("maximize"), "maximize");
+        assert_eq!(camel_case("is_maximized"), "isMaximized");
+        assert_eq!(camel_case("_set__pos_"), "setPos");
+    }
+}
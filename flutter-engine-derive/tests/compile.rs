@@ -0,0 +1,7 @@
+#[test]
+fn test_compile() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/client.rs");
+    t.compile_fail("tests/ui/no-client.rs");
+    t.compile_fail("tests/ui/result-error.rs");
+}
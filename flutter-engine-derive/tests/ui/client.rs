@@ -0,0 +1,44 @@
+use flutter_engine::channel::{MethodCall, MethodCallHandler, MethodChannel};
+use flutter_engine::codec::JSON_CODEC;
+use flutter_engine::error::{EngineError, InvokeError};
+use flutter_engine::method_channel;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+#[method_channel(name = "flutter-rs/dialog", client)]
+pub trait DialogHandler {
+    fn confirm(&mut self, title: String, detail_text: Option<String>) -> bool;
+
+    #[method(name = "close")]
+    fn dismiss(&mut self);
+
+    fn select(&mut self, title: String, options: HashMap<(i32, i32), String>) -> i32;
+}
+
+struct Handler;
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, _call: MethodCall) {}
+}
+
+fn main() {
+    let client = DialogHandlerClient::new(Weak::new());
+    let result = client.confirm("Quit?".to_owned(), None).wait();
+    assert!(matches!(
+        result,
+        Err(InvokeError::Engine(EngineError::EngineDropped))
+    ));
+    let result = client.dismiss().wait();
+    assert!(matches!(
+        result,
+        Err(InvokeError::Engine(EngineError::EngineDropped))
+    ));
+
+    // maps with non-string keys can't be represented as a `Value`
+    let channel = Arc::new(MethodChannel::new("flutter-rs/dialog", Handler, &JSON_CODEC));
+    let client = DialogHandlerClient::new(Arc::downgrade(&channel));
+    let mut options = HashMap::new();
+    options.insert((1, 2), "Yes".to_owned());
+    let result = client.select("Quit?".to_owned(), options).wait();
+    assert!(matches!(result, Err(InvokeError::SerializeError(_))));
+}
@@ -0,0 +1,11 @@
+use flutter_engine::method_channel;
+use std::sync::Weak;
+
+#[method_channel(name = "flutter-rs/window")]
+pub trait WindowHandler {
+    fn close(&mut self);
+}
+
+fn main() {
+    let _ = WindowHandlerClient::new(Weak::new());
+}
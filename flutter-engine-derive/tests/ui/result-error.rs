@@ -0,0 +1,8 @@
+use flutter_engine::method_channel;
+
+#[method_channel(name = "flutter-rs/window")]
+pub trait WindowHandler {
+    fn is_maximized(&mut self) -> Result<bool, String>;
+}
+
+fn main() {}
@@ -0,0 +1,120 @@
+//! Loading of AOT compiled Dart code, which release and profile engines require.
+
+use libloading::Library;
+use memmap::{Mmap, MmapOptions};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub enum AotData {
+    /// A shared library containing the snapshot symbols, usually `app.so`.
+    Elf(PathBuf),
+    /// Snapshots in separate files.
+    Blobs {
+        vm_snapshot_data: PathBuf,
+        vm_snapshot_instructions: PathBuf,
+        isolate_snapshot_data: PathBuf,
+        isolate_snapshot_instructions: PathBuf,
+    },
+}
+
+struct Snapshot {
+    ptr: *const u8,
+    size: usize,
+}
+
+/// Owns the loaded snapshots, which have to outlive the engine.
+pub(crate) struct LoadedAotData {
+    vm_snapshot_data: Snapshot,
+    vm_snapshot_instructions: Snapshot,
+    isolate_snapshot_data: Snapshot,
+    isolate_snapshot_instructions: Snapshot,
+    _library: Option<Library>,
+    _mappings: Vec<Mmap>,
+}
+
+// the snapshots are never modified and live as long as the library or mappings
+unsafe impl Send for LoadedAotData {}
+unsafe impl Sync for LoadedAotData {}
+
+impl LoadedAotData {
+    pub(crate) fn load(data: &AotData) -> io::Result<Self> {
+        match data {
+            AotData::Elf(path) => Self::load_elf(path),
+            AotData::Blobs {
+                vm_snapshot_data,
+                vm_snapshot_instructions,
+                isolate_snapshot_data,
+                isolate_snapshot_instructions,
+            } => {
+                let mappings = vec![
+                    map_file(vm_snapshot_data, false)?,
+                    map_file(vm_snapshot_instructions, true)?,
+                    map_file(isolate_snapshot_data, false)?,
+                    map_file(isolate_snapshot_instructions, true)?,
+                ];
+                let snapshot = |index: usize| Snapshot {
+                    ptr: mappings[index].as_ptr(),
+                    size: mappings[index].len(),
+                };
+                Ok(Self {
+                    vm_snapshot_data: snapshot(0),
+                    vm_snapshot_instructions: snapshot(1),
+                    isolate_snapshot_data: snapshot(2),
+                    isolate_snapshot_instructions: snapshot(3),
+                    _library: None,
+                    _mappings: mappings,
+                })
+            }
+        }
+    }
+
+    fn load_elf(path: &Path) -> io::Result<Self> {
+        let library = Library::new(path)?;
+        // the engine accepts a size of 0 for snapshots that are symbol references
+        let symbol = |name: &[u8]| -> io::Result<Snapshot> {
+            let symbol = unsafe { library.get::<*const u8>(name)? };
+            Ok(Snapshot {
+                ptr: *symbol,
+                size: 0,
+            })
+        };
+        Ok(Self {
+            vm_snapshot_data: symbol(b"kDartVmSnapshotData\0")?,
+            vm_snapshot_instructions: symbol(b"kDartVmSnapshotInstructions\0")?,
+            isolate_snapshot_data: symbol(b"kDartIsolateSnapshotData\0")?,
+            isolate_snapshot_instructions: symbol(b"kDartIsolateSnapshotInstructions\0")?,
+            _library: Some(library),
+            _mappings: vec![],
+        })
+    }
+
+    /// Pointers and sizes of the vm data, vm instructions, isolate data and isolate instructions
+    /// snapshots, in that order.
+    pub(crate) fn snapshots(&self) -> [(*const u8, usize); 4] {
+        let snapshot = |snapshot: &Snapshot| (snapshot.ptr, snapshot.size);
+        [
+            snapshot(&self.vm_snapshot_data),
+            snapshot(&self.vm_snapshot_instructions),
+            snapshot(&self.isolate_snapshot_data),
+            snapshot(&self.isolate_snapshot_instructions),
+        ]
+    }
+}
+
+fn map_file(path: &Path, executable: bool) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+    let options = MmapOptions::new();
+    unsafe {
+        if executable {
+            options.map_exec(&file)
+        } else {
+            options.map(&file)
+        }
+    }
+}
+
+/// Whether the linked engine is a release or profile build, which only run AOT compiled code.
+pub fn engine_runs_aot_compiled_dart_code() -> bool {
+    unsafe { flutter_engine_sys::FlutterEngineRunsAOTCompiledDartCode() }
+}
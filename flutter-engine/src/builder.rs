@@ -1,13 +1,29 @@
-use crate::tasks::TaskRunnerHandler;
-use crate::{CreateError, FlutterEngine, FlutterOpenGLHandler};
+use crate::aot::AotData;
+use crate::cache::PersistentCache;
+use crate::headless::HeadlessEngine;
+use crate::switches::EngineSwitches;
+use crate::tasks::{Clock, SystemClock, TaskRunnerHandler};
+use crate::{
+    CreateError, FlutterEngine, FlutterOpenGLHandler, FlutterSoftwareHandler, RendererHandler,
+    VsyncHandler,
+};
+use log::warn;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::thread::ThreadId;
 
 pub struct FlutterEngineBuilder {
     pub(crate) platform_handler: Option<Arc<dyn TaskRunnerHandler + Send + Sync>>,
-    pub(crate) opengl_handler: Option<Box<dyn FlutterOpenGLHandler + Send>>,
+    pub(crate) render_handler: Option<(Arc<dyn TaskRunnerHandler + Send + Sync>, ThreadId)>,
+    pub(crate) clock: Arc<dyn Clock + Send + Sync>,
+    pub(crate) renderer: Option<RendererHandler>,
+    pub(crate) vsync_handler: Option<Arc<dyn VsyncHandler + Send + Sync>>,
     pub(crate) assets: PathBuf,
     pub(crate) args: Vec<String>,
+    pub(crate) switches: EngineSwitches,
+    pub(crate) entrypoint: Option<String>,
+    pub(crate) persistent_cache: Option<PersistentCache>,
+    pub(crate) aot_data: Option<AotData>,
 }
 
 impl FlutterEngineBuilder {
@@ -15,9 +31,16 @@ impl FlutterEngineBuilder {
     pub fn new() -> Self {
         Self {
             platform_handler: None,
-            opengl_handler: None,
+            render_handler: None,
+            clock: Arc::new(SystemClock),
+            renderer: None,
+            vsync_handler: None,
             assets: Default::default(),
             args: vec![],
+            switches: Default::default(),
+            entrypoint: None,
+            persistent_cache: None,
+            aot_data: None,
         }
     }
 
@@ -29,11 +52,45 @@ impl FlutterEngineBuilder {
         self
     }
 
+    /// Run render tasks on the given thread instead of a thread managed by the engine.
+    /// The embedder is responsible for calling `FlutterEngine::execute_render_tasks` on that
+    /// thread whenever the handler is woken up.
+    pub fn with_render_handler(
+        mut self,
+        handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+        thread_id: ThreadId,
+    ) -> Self {
+        self.render_handler = Some((handler, thread_id));
+        self
+    }
+
+    /// Schedule the tasks of the platform and render runners according to `clock` instead of
+    /// the system clock, e.g. a `ManualClock` in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock + Send + Sync>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     pub fn with_opengl<H>(mut self, handler: H) -> Self
     where
         H: FlutterOpenGLHandler + Send + 'static,
     {
-        self.opengl_handler = Some(Box::new(handler));
+        self.renderer = Some(RendererHandler::OpenGL(Box::new(handler)));
+        self
+    }
+
+    pub fn with_software<H>(mut self, handler: H) -> Self
+    where
+        H: FlutterSoftwareHandler + Send + 'static,
+    {
+        self.renderer = Some(RendererHandler::Software(Box::new(handler)));
+        self
+    }
+
+    /// Let the embedder drive frame scheduling. Without a vsync handler the engine falls back to
+    /// its own timer.
+    pub fn with_vsync_handler(mut self, handler: Arc<dyn VsyncHandler + Send + Sync>) -> Self {
+        self.vsync_handler = Some(handler);
         self
     }
 
@@ -42,6 +99,52 @@ impl FlutterEngineBuilder {
         self
     }
 
+    /// Load AOT compiled Dart code, which is required by release and profile engines.
+    /// Debug engines run the kernel snapshot from the asset path instead.
+    pub fn with_aot_data(mut self, data: AotData) -> Self {
+        self.aot_data = Some(data);
+        self
+    }
+
+    /// Run the given Dart function instead of `main`. The function has to be annotated with
+    /// `@pragma('vm:entry-point')` so it is not removed by tree shaking.
+    pub fn with_entrypoint(mut self, entrypoint: &str) -> Self {
+        self.entrypoint = Some(entrypoint.to_owned());
+        self
+    }
+
+    /// Keep compiled shaders between launches in `cache`. Without a cache every launch compiles
+    /// its shaders again.
+    pub fn with_persistent_cache(mut self, cache: PersistentCache) -> Self {
+        self.persistent_cache = Some(cache);
+        self
+    }
+
+    /// Keep compiled shaders in the user's cache directory, see `PersistentCache::default_for_app`.
+    /// No cache is used if that directory can't be determined.
+    pub fn with_default_persistent_cache(mut self) -> Self {
+        self.persistent_cache = PersistentCache::default_for_app();
+        if self.persistent_cache.is_none() {
+            warn!("No cache directory found, compiled shaders are not kept");
+        }
+        self
+    }
+
+    /// Don't keep compiled shaders between launches, which is the default.
+    pub fn without_persistent_cache(mut self) -> Self {
+        self.persistent_cache = None;
+        self
+    }
+
+    /// Switches set in the `FLUTTER_ENGINE_SWITCHES` environment variable are applied on top of
+    /// these, see `EngineSwitches::from_env`.
+    pub fn with_switches(mut self, switches: EngineSwitches) -> Self {
+        self.switches = switches;
+        self
+    }
+
+    /// Pass a raw command line argument to the engine, prefer `with_switches` for switches
+    /// covered by `EngineSwitches`.
     pub fn with_arg(mut self, arg: String) -> Self {
         self.args.push(arg);
         self
@@ -57,4 +160,9 @@ impl FlutterEngineBuilder {
     pub fn build(self) -> Result<FlutterEngine, CreateError> {
         FlutterEngine::new(self)
     }
+
+    /// Start an engine without a window on its own platform thread, see `HeadlessEngine`.
+    pub fn build_headless(self) -> Result<HeadlessEngine, CreateError> {
+        HeadlessEngine::new(self)
+    }
 }
@@ -0,0 +1,298 @@
+//! The persistent cache, which keeps compiled shaders between launches of the app.
+
+use crate::builder::FlutterEngineBuilder;
+use crate::error::EngineError;
+use crate::headless::HeadlessTaskHandler;
+use crate::{CreateError, FlutterOpenGLHandler, FlutterSoftwareHandler, RendererHandler};
+use log::{debug, error, warn};
+use std::env;
+use std::fs;
+use std::io;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PersistentCache {
+    path: PathBuf,
+    read_only: bool,
+}
+
+impl PersistentCache {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            read_only: false,
+        }
+    }
+
+    /// The cache in the user's cache directory, e.g. `~/.cache/<app>/flutter` on Linux, where
+    /// `<app>` is the name of the executable.
+    pub fn default_for_app() -> Option<Self> {
+        let exe = env::current_exe().ok()?;
+        let app = exe.file_stem()?;
+        let path = dirs::cache_dir()?.join(app).join("flutter");
+        Some(Self::new(path))
+    }
+
+    /// A read-only cache is used as is, e.g. for a cache that was pre-seeded and shipped with
+    /// the app.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Run the app of `builder` with this cache until it presented its first frame or `timeout`
+    /// passed, so the shaders compiled for it are stored in the cache. `EngineSwitches::cache_sksl`
+    /// is enabled for the run, so the cache can be exported and shipped afterwards.
+    ///
+    /// The app is rendered at `size` physical pixels with `pixel_ratio`, which should match the
+    /// window of the app, as the engine doesn't render frames without a size. Shaders are only
+    /// compiled by the OpenGL renderer, so the builder should use the renderer of the app. The
+    /// engine runs on the calling thread, the platform handler of the builder is replaced.
+    /// Returns whether a frame was presented before the timeout.
+    pub fn warm_up(
+        &self,
+        builder: FlutterEngineBuilder,
+        size: (usize, usize),
+        pixel_ratio: f64,
+        timeout: Duration,
+    ) -> Result<bool, CreateError> {
+        if self.read_only {
+            warn!("Warming up a read-only persistent cache, no shaders will be stored");
+        }
+        let deadline = Instant::now() + timeout;
+        let handler = Arc::new(HeadlessTaskHandler::default());
+        let presented = Arc::new(AtomicBool::new(false));
+
+        let mut switches = builder.switches.clone();
+        switches.cache_sksl = true;
+        let mut builder = builder
+            .with_platform_handler(handler.clone())
+            .with_persistent_cache(self.clone())
+            .with_switches(switches);
+        builder.renderer = builder
+            .renderer
+            .map(|renderer| track_present(renderer, presented.clone()));
+        let engine = builder.build()?;
+
+        let result = engine
+            .run()
+            .and_then(|_| {
+                engine
+                    .send_window_metrics_event(size.0, size.1, pixel_ratio)
+                    .wait()
+            })
+            .map_err(CreateError::Engine);
+        if result.is_ok() {
+            wait_for_present(&presented, &handler, deadline, || {
+                engine.execute_platform_tasks()
+            });
+        }
+
+        if let Err(err) = engine.deinitialize() {
+            debug!("Failed to deinitialize engine: {}", err);
+        }
+        if let Err(err) = engine.shutdown() {
+            error!("Failed to shut down engine: {}", err);
+        }
+        result.map(|_| presented.load(Ordering::Acquire))
+    }
+
+    /// Copy the cache to `destination`, e.g. after a warm up run with
+    /// `EngineSwitches::cache_sksl` enabled.
+    pub fn export(&self, destination: &Path) -> io::Result<()> {
+        copy_dir(&self.path, destination)
+    }
+
+    /// Seed the cache with the contents of a previously exported cache. Existing entries with the
+    /// same name are replaced.
+    pub fn import(&self, source: &Path) -> io::Result<()> {
+        copy_dir(source, &self.path)
+    }
+
+    /// Remove all entries. This should not be called while an engine is using the cache.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Execute platform tasks until a frame was presented or `deadline` passed.
+fn wait_for_present<F>(
+    presented: &AtomicBool,
+    handler: &HeadlessTaskHandler,
+    deadline: Instant,
+    mut execute_platform_tasks: F,
+) where
+    F: FnMut() -> Result<Option<Instant>, EngineError>,
+{
+    while !presented.load(Ordering::Acquire) && Instant::now() < deadline {
+        match execute_platform_tasks() {
+            Ok(next_task_time) => {
+                let until = match next_task_time {
+                    Some(next_task_time) if next_task_time < deadline => next_task_time,
+                    _ => deadline,
+                };
+                handler.wait(Some(until));
+            }
+            Err(err) => {
+                error!("Failed to execute platform tasks: {}", err);
+                break;
+            }
+        }
+    }
+}
+
+fn copy_dir(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Forwards to the renderer of the app and records whether a frame was presented.
+struct PresentTracker<H> {
+    handler: H,
+    presented: Arc<AtomicBool>,
+}
+
+impl<H> PresentTracker<H> {
+    fn record(&self, presented: bool) -> bool {
+        if presented {
+            self.presented.store(true, Ordering::Release);
+        }
+        presented
+    }
+}
+
+fn track_present(renderer: RendererHandler, presented: Arc<AtomicBool>) -> RendererHandler {
+    match renderer {
+        RendererHandler::OpenGL(handler) => {
+            RendererHandler::OpenGL(Box::new(PresentTracker { handler, presented }))
+        }
+        RendererHandler::Software(handler) => {
+            RendererHandler::Software(Box::new(PresentTracker { handler, presented }))
+        }
+    }
+}
+
+impl FlutterOpenGLHandler for PresentTracker<Box<dyn FlutterOpenGLHandler + Send>> {
+    fn swap_buffers(&self) -> bool {
+        self.record(self.handler.swap_buffers())
+    }
+
+    fn make_current(&self) -> bool {
+        self.handler.make_current()
+    }
+
+    fn clear_current(&self) -> bool {
+        self.handler.clear_current()
+    }
+
+    fn fbo_callback(&self) -> u32 {
+        self.handler.fbo_callback()
+    }
+
+    fn make_resource_current(&self) -> bool {
+        self.handler.make_resource_current()
+    }
+
+    fn gl_proc_resolver(&self, proc: *const c_char) -> *mut c_void {
+        self.handler.gl_proc_resolver(proc)
+    }
+}
+
+impl FlutterSoftwareHandler for PresentTracker<Box<dyn FlutterSoftwareHandler + Send>> {
+    fn present(&self, buffer: &[u8], row_bytes: usize, height: usize) -> bool {
+        self.record(self.handler.present(buffer, row_bytes, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Frames;
+
+    impl FlutterSoftwareHandler for Frames {
+        fn present(&self, _buffer: &[u8], _row_bytes: usize, _height: usize) -> bool {
+            true
+        }
+    }
+
+    fn present(renderer: &RendererHandler) -> bool {
+        match renderer {
+            RendererHandler::Software(handler) => handler.present(&[0; 16], 16, 1),
+            RendererHandler::OpenGL(handler) => handler.swap_buffers(),
+        }
+    }
+
+    #[test]
+    fn test_warm_up_presents() {
+        let presented = Arc::new(AtomicBool::new(false));
+        let renderer = track_present(
+            RendererHandler::Software(Box::new(Frames)),
+            presented.clone(),
+        );
+        let handler = HeadlessTaskHandler::default();
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        let mut runs = 0;
+        wait_for_present(&presented, &handler, deadline, || {
+            runs += 1;
+            // the first frame is rendered once the window metrics were processed
+            if runs == 3 {
+                present(&renderer);
+            }
+            Ok(Some(Instant::now()))
+        });
+        assert!(presented.load(Ordering::Acquire));
+        assert_eq!(runs, 3);
+    }
+
+    #[test]
+    fn test_warm_up_times_out() {
+        let presented = Arc::new(AtomicBool::new(false));
+        let handler = HeadlessTaskHandler::default();
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+
+        wait_for_present(&presented, &handler, deadline, || Ok(None));
+        assert!(!presented.load(Ordering::Acquire));
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_warm_up_stops_on_error() {
+        let presented = AtomicBool::new(false);
+        let handler = HeadlessTaskHandler::default();
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        let mut runs = 0;
+        wait_for_present(&presented, &handler, deadline, || {
+            runs += 1;
+            Err(EngineError::NotOnPlatformThread)
+        });
+        assert_eq!(runs, 1);
+    }
+}
@@ -1,63 +1,283 @@
-use std::sync::{Arc, RwLock, Weak};
+use log::{error, trace};
 
-use log::error;
-
-use crate::{
-    channel::{ChannelImpl, EventHandler, MethodCallHandler, MethodChannel},
-    codec::{standard_codec::CODEC, MethodCall, MethodCodec, Value},
-    error::MethodCallError,
-    FlutterEngine, FlutterEngineWeakRef,
-};
-use crate::channel::method_channel::MethodCallHandler;
+use crate::channel::platform_message::PlatformMessage;
+use crate::channel::{Channel, MethodCall, MethodError};
+use crate::codec::value::{self, to_value};
+use crate::codec::{MethodCodec, Value};
+use crate::completion::{completion, CompletionHandle};
+use crate::error::{EngineError, EventError};
+use crate::{FlutterEngine, FlutterEngineWeakRef};
+use futures_core::Stream;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
 
+/// Handles the listeners of an `EventChannel`.
 pub trait EventHandler {
-    fn on_listen(&mut self, args: Value, engine: FlutterEngine) -> Result<Value, MethodCallError>;
-    fn on_cancel(&mut self, engine: FlutterEngine) -> Result<Value, MethodCallError>;
+    /// Called whenever Dart starts listening to the stream, with the arguments passed to
+    /// `receiveBroadcastStream`. Every listen gets its own sink, events are sent through it until
+    /// that listen is cancelled.
+    fn on_listen(&mut self, args: Value, sink: EventSink) -> Result<(), MethodError<Value>>;
+
+    /// Called when Dart stops listening, with the sink of the listen that ended. The sink is no
+    /// longer active, events sent through it afterwards are discarded.
+    fn on_cancel(&mut self, args: Value, sink: EventSink) -> Result<(), MethodError<Value>>;
+}
+
+/// Sends the events of one listen of an `EventChannel`. Sinks can be cloned and used from any
+/// thread, the events are sent on the platform thread.
+#[derive(Clone)]
+pub struct EventSink {
+    name: Arc<str>,
+    engine: FlutterEngineWeakRef,
+    codec: &'static dyn MethodCodec,
+    listen_id: u64,
+    active: Arc<AtomicBool>,
+}
+
+impl EventSink {
+    /// Identifies the listen of this sink, unique within its channel.
+    pub fn listen_id(&self) -> u64 {
+        self.listen_id
+    }
+
+    /// Whether Dart is still listening to the events of this sink.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    pub fn success<T>(&self, event: T) -> CompletionHandle<(), EventError>
+    where
+        T: Serialize,
+    {
+        match to_value(event) {
+            Ok(value) => self.send(self.codec.encode_success_envelope(&value)),
+            Err(err) => self.invalid_event(err),
+        }
+    }
+
+    pub fn error<T, S1, S2>(
+        &self,
+        code: S1,
+        message: S2,
+        details: T,
+    ) -> CompletionHandle<(), EventError>
+    where
+        T: Serialize,
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        match to_value(details) {
+            Ok(details) => self.send(self.codec.encode_error_envelope(
+                code.as_ref(),
+                message.as_ref(),
+                &details,
+            )),
+            Err(err) => self.invalid_event(err),
+        }
+    }
+
+    /// Close the stream on the Dart side. No events can be sent afterwards.
+    pub fn end_of_stream(&self) -> CompletionHandle<(), EventError> {
+        let handle = self.send(Vec::new());
+        self.close();
+        handle
+    }
+
+    /// Send every item of `stream` as an event and end the stream once it is exhausted. The
+    /// stream is polled on the platform thread, so this has to be called there, e.g. from
+    /// `EventHandler::on_listen`. It is dropped once Dart cancels the stream and the next
+    /// item is ready.
+    pub fn forward<S>(self, stream: S) -> CompletionHandle<()>
+    where
+        S: Stream + 'static,
+        S::Item: Serialize,
+    {
+        match self.engine.upgrade() {
+            Some(engine) => engine.spawn_local(Forward {
+                stream: Box::pin(stream),
+                sink: self,
+            }),
+            None => CompletionHandle::ready(Err(EngineError::EngineDropped)),
+        }
+    }
+
+    fn close(&self) {
+        self.active.store(false, Ordering::Release);
+    }
+
+    fn invalid_event(&self, err: value::Error) -> CompletionHandle<(), EventError> {
+        error!("Failed to encode event on channel {}: {}", self.name, err);
+        CompletionHandle::ready(Err(EventError::InvalidEvent))
+    }
+
+    fn send(&self, buf: Vec<u8>) -> CompletionHandle<(), EventError> {
+        if !self.is_active() {
+            return CompletionHandle::ready(Err(EventError::StreamClosed));
+        }
+        let engine = match self.engine.upgrade() {
+            Some(engine) => engine,
+            None => return CompletionHandle::ready(Err(EngineError::EngineDropped.into())),
+        };
+        let name = self.name.clone();
+        let (completer, handle) = completion();
+        engine.run_on_platform_thread(move |engine| {
+            let result = engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(&name),
+                message: &buf,
+                response_handle: None,
+            });
+            if let Err(err) = &result {
+                error!("Failed to send event on channel {}: {}", name, err);
+            }
+            completer.complete(result.map_err(EventError::from))
+        });
+        handle
+    }
+}
+
+struct Forward<S> {
+    stream: Pin<Box<S>>,
+    sink: EventSink,
+}
+
+impl<S> Future for Forward<S>
+where
+    S: Stream,
+    S::Item: Serialize,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if !self.sink.is_active() {
+                return Poll::Ready(());
+            }
+            match self.stream.as_mut().poll_next(cx) {
+                // a failed send means the listener is gone, which stops the loop on the next
+                // iteration through `is_active`
+                Poll::Ready(Some(item)) => {
+                    drop(self.sink.success(item));
+                }
+                Poll::Ready(None) => {
+                    drop(self.sink.end_of_stream());
+                    return Poll::Ready(());
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
 }
 
+/// The Rust side of an `EventChannel` in Dart. Dart listens to the channel through
+/// `receiveBroadcastStream`, every listen gets its own `EventSink`.
+///
+/// Dart cancels a listen with the arguments it was started with, so the active listens of a
+/// channel are told apart by their arguments. If Dart listens again with the arguments of an
+/// active listen, e.g. after a hot restart, the previous listen is cancelled before the handler
+/// is asked to start the new one.
 pub struct EventChannel {
     name: String,
     engine: FlutterEngineWeakRef,
-    method_handler: Arc<RwLock<dyn MethodCallHandler + Send + Sync>>,
-    plugin_name: Option<&'static str>,
+    event_handler: RefCell<Box<dyn EventHandler>>,
+    codec: &'static dyn MethodCodec,
+    listeners: RefCell<Vec<Listener>>,
+    next_listen_id: Cell<u64>,
 }
 
-struct EventChannelMethodCallHandler {
-    event_handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
+struct Listener {
+    args: Value,
+    sink: EventSink,
 }
 
 impl EventChannel {
-    pub fn new<N: AsRef<str>>(
-        name: N,
-        handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
-    ) -> Self {
+    pub fn new<N, H>(name: N, event_handler: H, codec: &'static dyn MethodCodec) -> Self
+    where
+        N: AsRef<str>,
+        H: EventHandler + 'static,
+    {
         Self {
             name: name.as_ref().to_owned(),
             engine: Default::default(),
-            method_handler: Arc::new(RwLock::new(EventChannelMethodCallHandler::new(handler))),
-            plugin_name: None,
+            event_handler: RefCell::new(Box::new(event_handler)),
+            codec,
+            listeners: RefCell::new(Vec::new()),
+            next_listen_id: Cell::new(1),
         }
     }
 
+    /// Whether Dart is currently listening to the stream.
+    pub fn is_listening(&self) -> bool {
+        self.listeners
+            .borrow()
+            .iter()
+            .any(|listener| listener.sink.is_active())
+    }
+
+    /// The number of listens whose stream was neither cancelled nor ended.
+    pub fn listener_count(&self) -> usize {
+        self.listeners
+            .borrow()
+            .iter()
+            .filter(|listener| listener.sink.is_active())
+            .count()
+    }
+
+    /// Remove the listen started with `args`.
+    fn take_listener(&self, args: &Value) -> Option<EventSink> {
+        let mut listeners = self.listeners.borrow_mut();
+        let index = listeners
+            .iter()
+            .position(|listener| listener.args == *args)?;
+        let listener = listeners.remove(index);
+        listener.sink.close();
+        Some(listener.sink)
+    }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a success event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_success_event(&self, data: &Value) {
-        let buf = self.codec().encode_success_envelope(data);
-        self.send_buffer(&buf);
+    fn cancel(&self, args: Value, sink: EventSink) -> Result<(), MethodError<Value>> {
+        self.event_handler.borrow_mut().on_cancel(args, sink)
     }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a error event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_error_event(&self, code: &str, message: &str, data: &Value) {
-        let buf = self.codec().encode_error_envelope(code, message, data);
-        self.send_buffer(&buf);
+    fn listen(&self, args: Value) -> Result<(), MethodError<Value>> {
+        if let Some(previous) = self.take_listener(&args) {
+            if let Err(MethodError::Err { message, .. }) = self.cancel(args.clone(), previous) {
+                error!(
+                    "Failed to cancel previous stream on channel {}: {}",
+                    self.name, message
+                );
+            }
+        }
+
+        let listen_id = self.next_listen_id.get();
+        self.next_listen_id.set(listen_id + 1);
+        let sink = EventSink {
+            name: self.name.as_str().into(),
+            engine: self.engine.clone(),
+            codec: self.codec,
+            listen_id,
+            active: Arc::new(AtomicBool::new(true)),
+        };
+        self.listeners.borrow_mut().push(Listener {
+            args: args.clone(),
+            sink: sink.clone(),
+        });
+        let result = self
+            .event_handler
+            .borrow_mut()
+            .on_listen(args.clone(), sink);
+        if result.is_err() {
+            self.take_listener(&args);
+        }
+        result
     }
 }
 
-impl ChannelImpl for EventChannel {
+impl Channel for EventChannel {
     fn name(&self) -> &str {
         self.name.as_str()
     }
@@ -66,52 +286,160 @@ impl ChannelImpl for EventChannel {
         self.engine.upgrade()
     }
 
-    fn init(&mut self, engine: FlutterEngineWeakRef, plugin_name: &'static str) {
+    fn init(&mut self, engine: FlutterEngineWeakRef) {
         if self.engine.upgrade().is_some() {
             error!("Channel {} was already initialized", self.name);
         }
         self.engine = engine;
-        self.plugin_name.replace(plugin_name);
     }
 
-    fn plugin_name(&self) -> &'static str {
-        self.plugin_name.unwrap()
+    /// Handle the `listen` and `cancel` calls of Dart
+    fn handle_platform_message(&self, msg: PlatformMessage) {
+        debug_assert_eq!(msg.channel, self.name());
+        let call = match self.codec.decode_method_call(msg.message) {
+            Some(call) => call,
+            None => {
+                error!("Failed to decode method call on channel {}", self.name);
+                if let Some(handle) = msg.response_handle {
+                    let buf = self.codec.encode_error_envelope(
+                        "error",
+                        "Failed to decode method call",
+                        &Value::Null,
+                    );
+                    self.send_response(handle, &buf);
+                }
+                return;
+            }
+        };
+        trace!(
+            "on channel {}, got method call {} with args {:?}",
+            self.name,
+            call.method,
+            call.args
+        );
+
+        let method = call.method.clone();
+        let args = call.args.clone();
+        let call = MethodCall::new(self.engine.clone(), self.codec, call, msg.response_handle);
+        match method.as_str() {
+            "listen" => call.respond(self.listen(args)),
+            "cancel" => match self.take_listener(&args) {
+                Some(sink) => call.respond(self.cancel(args, sink)),
+                None => call.error("error", "No active stream to cancel", Value::Null),
+            },
+            _ => call.not_implemented(),
+        }
     }
 }
 
-impl MethodChannel for EventChannel {
-    fn method_handler(&self) -> Option<Arc<RwLock<dyn MethodCallHandler + Send + Sync>>> {
-        Some(Arc::clone(&self.method_handler))
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::STANDARD_CODEC;
+    use std::rc::Rc;
 
-    fn codec(&self) -> &'static dyn MethodCodec {
-        &CODEC
+    #[derive(Default)]
+    struct Sinks {
+        listening: Vec<EventSink>,
+        cancelled: Vec<u64>,
     }
-}
 
-impl EventChannelMethodCallHandler {
-    pub fn new(handler: Weak<RwLock<dyn EventHandler + Send + Sync>>) -> Self {
-        Self {
-            event_handler: handler,
+    struct RecordingHandler(Rc<RefCell<Sinks>>);
+
+    impl EventHandler for RecordingHandler {
+        fn on_listen(&mut self, _args: Value, sink: EventSink) -> Result<(), MethodError<Value>> {
+            self.0.borrow_mut().listening.push(sink);
+            Ok(())
         }
-    }
-}
 
-impl MethodCallHandler for EventChannelMethodCallHandler {
-    fn on_method_call(
-        &mut self,
-        call: MethodCall,
-        engine: FlutterEngine,
-    ) -> Result<Value, MethodCallError> {
-        if let Some(handler) = self.event_handler.upgrade() {
-            let mut handler = handler.write().unwrap();
-            match call.method.as_str() {
-                "listen" => handler.on_listen(call.args, engine),
-                "cancel" => handler.on_cancel(engine),
-                _ => Err(MethodCallError::NotImplemented),
-            }
-        } else {
-            Err(MethodCallError::ChannelClosed)
+        fn on_cancel(&mut self, _args: Value, sink: EventSink) -> Result<(), MethodError<Value>> {
+            self.0.borrow_mut().cancelled.push(sink.listen_id());
+            Ok(())
         }
     }
-}
\ No newline at end of file
+
+    fn channel() -> (EventChannel, Rc<RefCell<Sinks>>) {
+        let sinks = Rc::new(RefCell::new(Sinks::default()));
+        let channel = EventChannel::new(
+            "flutter-rs/test",
+            RecordingHandler(sinks.clone()),
+            &STANDARD_CODEC,
+        );
+        (channel, sinks)
+    }
+
+    #[test]
+    fn test_listeners_get_their_own_sinks() {
+        let (channel, sinks) = channel();
+        assert!(channel.listen(Value::I32(1)).is_ok());
+        assert!(channel.listen(Value::I32(2)).is_ok());
+        assert_eq!(channel.listener_count(), 2);
+
+        let sinks = sinks.borrow();
+        assert_eq!(sinks.listening.len(), 2);
+        assert_ne!(
+            sinks.listening[0].listen_id(),
+            sinks.listening[1].listen_id()
+        );
+        assert!(sinks.listening.iter().all(EventSink::is_active));
+    }
+
+    #[test]
+    fn test_cancel_ends_the_listen_with_the_same_arguments() {
+        let (channel, sinks) = channel();
+        assert!(channel.listen(Value::I32(1)).is_ok());
+        assert!(channel.listen(Value::I32(2)).is_ok());
+
+        let sink = channel.take_listener(&Value::I32(2)).unwrap();
+        assert!(channel.cancel(Value::I32(2), sink).is_ok());
+        assert!(channel.take_listener(&Value::I32(3)).is_none());
+
+        let sinks = sinks.borrow();
+        assert_eq!(sinks.cancelled, vec![sinks.listening[1].listen_id()]);
+        assert!(sinks.listening[0].is_active());
+        assert!(!sinks.listening[1].is_active());
+        assert_eq!(channel.listener_count(), 1);
+    }
+
+    #[test]
+    fn test_listen_with_the_same_arguments_replaces_previous_listen() {
+        let (channel, sinks) = channel();
+        assert!(channel.listen(Value::Null).is_ok());
+        assert!(channel.listen(Value::Null).is_ok());
+
+        let sinks = sinks.borrow();
+        assert_eq!(sinks.cancelled, vec![sinks.listening[0].listen_id()]);
+        assert!(!sinks.listening[0].is_active());
+        assert!(sinks.listening[1].is_active());
+        assert_eq!(channel.listener_count(), 1);
+    }
+
+    #[test]
+    fn test_closed_sink_rejects_events() {
+        let (channel, sinks) = channel();
+        assert!(channel.listen(Value::Null).is_ok());
+        let sink = sinks.borrow().listening[0].clone();
+        assert_eq!(
+            sink.end_of_stream().wait(),
+            Err(EngineError::EngineDropped.into())
+        );
+        assert!(!channel.is_listening());
+        assert_eq!(sink.success(1).wait(), Err(EventError::StreamClosed));
+    }
+
+    #[test]
+    fn test_unserializable_event() {
+        let (channel, sinks) = channel();
+        assert!(channel.listen(Value::Null).is_ok());
+        let sink = sinks.borrow().listening[0].clone();
+        // maps with non-string keys can't be represented as a `Value`
+        let mut event = std::collections::HashMap::new();
+        event.insert((1, 2), 3);
+        assert_eq!(sink.success(&event).wait(), Err(EventError::InvalidEvent));
+        assert_eq!(
+            sink.error("code", "message", &event).wait(),
+            Err(EventError::InvalidEvent)
+        );
+        assert!(sink.is_active());
+    }
+}
@@ -1,13 +1,17 @@
 use crate::{codec::MessageCodec, FlutterEngine, FlutterEngineWeakRef};
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
+use crate::channel::response::{response, ResponseFuture};
 use crate::channel::Channel;
 use crate::codec::value::{from_value, from_value_owned, to_value};
 use crate::codec::Value;
+use crate::completion::CompletionHandle;
+use crate::error::{EngineError, InvokeError};
 use log::error;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::sync::Arc;
 
 pub struct Message {
     engine: FlutterEngineWeakRef,
@@ -39,7 +43,9 @@ impl Message {
             let value = to_value(data).expect("Failed to encode data to value");
             let buf = self.codec.encode_message(&value);
             engine.run_on_platform_thread(move |eng| {
-                eng.send_platform_message_response(handle, &buf);
+                if let Err(err) = eng.send_platform_message_response(handle, &buf) {
+                    error!("Failed to send response: {}", err);
+                }
             });
         }
     }
@@ -79,27 +85,35 @@ impl MessageChannel {
     }
 
     /// Send a value on this channel.
-    pub fn send<T>(&self, value: T)
+    /// Can be called from any thread, the message is sent on the platform thread.
+    pub fn send<T>(&self, value: T) -> CompletionHandle<()>
     where
         T: Serialize,
     {
         if let Some(engine) = self.engine() {
-            if !engine.is_platform_thread() {
-                panic!("Not on platform thread");
-            }
-
             let buf = self
                 .codec()
                 .encode_message(&to_value(value).expect("Failed to encode value"));
+            let name = self.name().to_owned();
 
-            engine.send_platform_message(PlatformMessage {
-                channel: Cow::Borrowed(self.name()),
-                message: &buf,
-                response_handle: None,
-            });
+            engine.run_on_platform_thread_with_completion(move |engine| {
+                let result = engine.send_platform_message(PlatformMessage {
+                    channel: Cow::Borrowed(&name),
+                    message: &buf,
+                    response_handle: None,
+                });
+                if let Err(err) = &result {
+                    error!("Failed to send message on channel {}: {}", name, err);
+                }
+                result
+            })
+        } else {
+            CompletionHandle::ready(Err(EngineError::EngineDropped))
         }
     }
 
+    /// Can be called from any thread, the message is sent on the platform thread. `callback` is
+    /// only called with a successfully decoded response, errors are logged.
     pub fn send_with_result<T, F, V>(&self, value: T, callback: F)
     where
         T: Serialize,
@@ -107,25 +121,90 @@ impl MessageChannel {
         V: DeserializeOwned,
     {
         if let Some(engine) = self.engine() {
-            if !engine.is_platform_thread() {
-                panic!("Not on platform thread");
-            }
-
             let codec = self.codec;
-            let buf = codec.encode_message(&to_value(value).unwrap());
+            let value = match to_value(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    error!("Failed to encode message on channel {}: {}", self.name, err);
+                    return;
+                }
+            };
+            let buf = codec.encode_message(&value);
+            let name = self.name().to_owned();
 
-            let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
-                let resp = codec.decode_message(data);
-                let val = resp.unwrap();
-                callback(from_value_owned(&val).unwrap());
+            engine.run_on_platform_thread(move |engine| {
+                let response_name = name.clone();
+                let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
+                    match decode_message_response(codec, data) {
+                        Ok(value) => callback(value),
+                        Err(err) => error!(
+                            "Failed to receive response on channel {}: {}",
+                            response_name, err
+                        ),
+                    }
+                });
+
+                if let Err(err) = engine.send_platform_message(PlatformMessage {
+                    channel: Cow::Borrowed(&name),
+                    message: &buf,
+                    response_handle: Some(handle),
+                }) {
+                    error!("Failed to send message on channel {}: {}", name, err);
+                }
             });
+        }
+    }
+
+    /// Send a value on this channel and wait for the response of dart.
+    /// Can be called from any thread, the message is sent on the platform thread.
+    pub fn send_async<T, V>(&self, value: T) -> ResponseFuture<V>
+    where
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+    {
+        let value = match to_value(value) {
+            Ok(value) => value,
+            Err(err) => return ResponseFuture::ready(Err(InvokeError::SerializeError(err))),
+        };
+        let engine = match self.engine() {
+            Some(engine) => engine,
+            None => return ResponseFuture::ready(Err(EngineError::EngineDropped.into())),
+        };
+        let codec = self.codec;
+        let buf = codec.encode_message(&value);
+        let name = self.name().to_owned();
+        let (responder, future) = response();
+        let responder = Arc::new(responder);
 
-            engine.send_platform_message(PlatformMessage {
-                channel: Cow::Borrowed(self.name()),
+        engine.run_on_platform_thread(move |engine| {
+            let callback_responder = responder.clone();
+            let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
+                callback_responder.complete(decode_message_response(codec, data));
+            });
+            if let Err(err) = engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(&name),
                 message: &buf,
                 response_handle: Some(handle),
-            });
-        }
+            }) {
+                error!("Failed to send message on channel {}: {}", name, err);
+                responder.complete(Err(err.into()));
+            }
+        });
+        future
+    }
+}
+
+fn decode_message_response<V>(codec: &dyn MessageCodec, data: &[u8]) -> Result<V, InvokeError>
+where
+    V: DeserializeOwned,
+{
+    // the engine responds with an empty message if dart has no handler for the channel
+    if data.is_empty() {
+        return Err(InvokeError::NoHandler);
+    }
+    match codec.decode_message(data) {
+        Some(value) => Ok(from_value_owned(&value)?),
+        None => Err(InvokeError::InvalidResponse),
     }
 }
 
@@ -163,3 +242,27 @@ impl Channel for MessageChannel {
         self.message_handler.borrow_mut().on_message(msg);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::value::Value;
+    use crate::codec::STRING_CODEC;
+    use std::collections::HashMap;
+
+    struct Handler;
+
+    impl MessageHandler for Handler {
+        fn on_message(&mut self, _msg: Message) {}
+    }
+
+    #[test]
+    fn test_send_unserializable_value() {
+        let channel = MessageChannel::new("test", Handler, &STRING_CODEC);
+        // maps with non-string keys can't be represented as a `Value`
+        let mut value = HashMap::new();
+        value.insert((1, 2), 3);
+        let result = channel.send_async::<_, Value>(value).wait();
+        assert!(matches!(result, Err(InvokeError::SerializeError(_))));
+    }
+}
@@ -1,15 +1,22 @@
-use log::error;
+use log::{error, warn};
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
+use crate::channel::response::{response, ResponseFuture};
 use crate::channel::Channel;
 use crate::{codec, codec::MethodCodec, FlutterEngine, FlutterEngineWeakRef};
 
 use crate::codec::value::{from_value, from_value_owned, to_value};
 use crate::codec::{MethodCallResult, Value};
+use crate::completion::CompletionHandle;
+use crate::error::{EngineError, InvokeError, ValueError};
+use parking_lot::Mutex;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 pub struct MethodCall {
     engine: FlutterEngineWeakRef,
@@ -31,6 +38,20 @@ where
 }
 
 impl MethodCall {
+    pub(super) fn new(
+        engine: FlutterEngineWeakRef,
+        codec: &'static dyn MethodCodec,
+        inner: codec::MethodCall,
+        response_handle: Option<PlatformMessageResponseHandle>,
+    ) -> Self {
+        Self {
+            engine,
+            codec,
+            inner,
+            response_handle,
+        }
+    }
+
     pub fn args<'a, T>(&'a self) -> T
     where
         T: Deserialize<'a>,
@@ -38,6 +59,26 @@ impl MethodCall {
         from_value(&self.inner.args).unwrap()
     }
 
+    /// Like `args`, but returns an error if the arguments don't match `T`.
+    pub fn try_args<T>(&self) -> Result<T, ValueError>
+    where
+        T: DeserializeOwned,
+    {
+        from_value_owned(&self.inner.args)
+    }
+
+    /// Get the argument `name` of a call whose arguments are a map. A missing argument is
+    /// deserialized from null, so optional arguments can be read as `Option`.
+    pub fn try_arg<T>(&self, name: &str) -> Result<T, ValueError>
+    where
+        T: DeserializeOwned,
+    {
+        match &self.inner.args {
+            Value::Map(args) => from_value_owned(args.get(name).unwrap_or(&Value::Null)),
+            _ => Err(ValueError::NoMap),
+        }
+    }
+
     pub fn raw_args(&self) -> &Value {
         &self.inner.args
     }
@@ -55,11 +96,17 @@ impl MethodCall {
         T: Serialize,
         D: Serialize + DeserializeOwned,
     {
+        let handle = match self.response_handle {
+            Some(handle) => handle,
+            None => {
+                warn!(
+                    "Method call {} can not be responded to, ignoring response",
+                    self.inner.method
+                );
+                return;
+            }
+        };
         if let Some(engine) = self.engine.upgrade() {
-            let handle = self
-                .response_handle
-                .expect("Message can not be response handle");
-
             let result = match result {
                 Ok(val) => {
                     let value = to_value(val).expect("Failed to encode data to value");
@@ -84,7 +131,9 @@ impl MethodCall {
 
             let buf = self.codec.encode_method_call_response(&result);
             engine.run_on_platform_thread(move |eng| {
-                eng.send_platform_message_response(handle, &buf);
+                if let Err(err) = eng.send_platform_message_response(handle, &buf) {
+                    error!("Failed to send response: {}", err);
+                }
             });
         }
     }
@@ -126,6 +175,50 @@ pub trait MethodCallHandler {
     fn on_method_call(&mut self, call: MethodCall);
 }
 
+pub type MethodCallFuture = Pin<Box<dyn Future<Output = Result<Value, MethodError<Value>>>>>;
+
+/// A handler whose calls complete asynchronously. The returned futures run on the platform
+/// thread and the call is answered with their output, so they must not block. Each call gets its
+/// own future, so calls can complete in any order.
+///
+/// The `MethodCall` passed to the handler can't respond, the response is sent by the channel.
+/// Responding to it anyway is ignored.
+pub trait AsyncMethodCallHandler {
+    fn on_method_call(&mut self, call: MethodCall) -> MethodCallFuture;
+}
+
+struct AsyncHandlerAdapter<H>(H);
+
+impl<H> MethodCallHandler for AsyncHandlerAdapter<H>
+where
+    H: AsyncMethodCallHandler,
+{
+    fn on_method_call(&mut self, mut call: MethodCall) {
+        let engine = match call.engine.upgrade() {
+            Some(engine) => engine,
+            None => return,
+        };
+        let responder = MethodCall::new(
+            call.engine.clone(),
+            call.codec,
+            codec::MethodCall {
+                method: call.inner.method.clone(),
+                args: Value::Null,
+            },
+            call.response_handle.take(),
+        );
+        let future = self.0.on_method_call(call);
+        // calls are handled on the platform thread, so spawning can't fail, and the future
+        // responds by itself
+        drop(engine.spawn_local(async move {
+            let result = future.await;
+            if responder.can_respond() {
+                responder.respond(result);
+            }
+        }));
+    }
+}
+
 pub struct MethodChannel {
     name: String,
     engine: FlutterEngineWeakRef,
@@ -147,12 +240,21 @@ impl MethodChannel {
         }
     }
 
+    pub fn new_async<N, H>(name: N, method_handler: H, codec: &'static dyn MethodCodec) -> Self
+    where
+        N: AsRef<str>,
+        H: AsyncMethodCallHandler + 'static,
+    {
+        Self::new(name, AsyncHandlerAdapter(method_handler), codec)
+    }
+
     fn codec(&self) -> &'static dyn MethodCodec {
         self.codec
     }
 
     /// Invoke a flutter method using this channel
-    pub fn invoke_method<S, T>(&self, method: S, args: T)
+    /// Can be called from any thread, the method call is sent on the platform thread.
+    pub fn invoke_method<S, T>(&self, method: S, args: T) -> CompletionHandle<()>
     where
         S: Into<String>,
         T: Serialize,
@@ -162,16 +264,42 @@ impl MethodChannel {
                 method: method.into(),
                 args: to_value(args).expect("Failed to encode args to value"),
             });
-
-            engine.send_platform_message(PlatformMessage {
-                channel: Cow::Borrowed(self.name()),
-                message: &buf,
-                response_handle: None,
-            });
+            let name = self.name().to_owned();
+
+            engine.run_on_platform_thread_with_completion(move |engine| {
+                let result = engine.send_platform_message(PlatformMessage {
+                    channel: Cow::Borrowed(&name),
+                    message: &buf,
+                    response_handle: None,
+                });
+                if let Err(err) = &result {
+                    error!("Failed to send message on channel {}: {}", name, err);
+                }
+                result
+            })
+        } else {
+            CompletionHandle::ready(Err(EngineError::EngineDropped))
         }
     }
 
-    /// Invoke a flutter method using this channel
+    /// Invoke a flutter method using this channel and wait for its result.
+    /// Can be called from any thread, the method call is sent on the platform thread.
+    pub fn invoke_method_async<S, T, V>(&self, method: S, args: T) -> ResponseFuture<V>
+    where
+        S: Into<String>,
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+    {
+        let (responder, future) = response();
+        self.send_method_call(method.into(), args, move |result| {
+            responder.complete(result)
+        });
+        future
+    }
+
+    /// Invoke a flutter method using this channel and call `callback` with its result.
+    /// Can be called from any thread, the method call is sent on the platform thread. `callback`
+    /// is only called with a response of dart, other errors are logged.
     pub fn invoke_method_with_result<T, F, V, D>(&self, method: String, args: T, callback: F)
     where
         T: Serialize,
@@ -179,45 +307,102 @@ impl MethodChannel {
         V: DeserializeOwned,
         D: DeserializeOwned + Serialize,
     {
-        if let Some(engine) = self.engine() {
-            let codec = self.codec;
-
-            let buf = codec.encode_method_call(&codec::MethodCall {
-                method,
-                args: to_value(args).expect("Failed to encode args to value"),
-            });
-
-            let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
-                let result = codec
-                    .decode_envelope(data)
-                    .expect("Failed to decode response envelope");
-
-                let response = match result {
-                    MethodCallResult::Ok(val) => {
-                        Ok(from_value_owned(&val).expect("Failed to decode success response"))
-                    }
-                    MethodCallResult::Err {
+        let name = self.name().to_owned();
+        self.send_method_call(method, args, move |result| {
+            let result = match result {
+                Ok(value) => Ok(value),
+                Err(InvokeError::NotImplemented) => Err(MethodError::NotImplemented),
+                Err(InvokeError::CustomError {
+                    code,
+                    message,
+                    details,
+                }) => match from_value_owned(&details) {
+                    Ok(details) => Err(MethodError::Err {
                         code,
                         message,
                         details,
-                    } => Err(MethodError::Err {
-                        code,
-                        message,
-                        details: from_value_owned(&details)
-                            .expect("Failed to deserialize error details"),
                     }),
-                    MethodCallResult::NotImplemented => Err(MethodError::NotImplemented),
-                };
+                    Err(err) => {
+                        error!(
+                            "Failed to decode error details on channel {}: {}",
+                            name, err
+                        );
+                        return;
+                    }
+                },
+                Err(err) => {
+                    error!("Failed to invoke method on channel {}: {}", name, err);
+                    return;
+                }
+            };
+            callback(result);
+        });
+    }
 
-                callback(response);
-            });
+    /// Send a method call on the platform thread. `on_response` is called with the decoded
+    /// response, or with the error that kept the call from being sent.
+    fn send_method_call<T, V, F>(&self, method: String, args: T, on_response: F)
+    where
+        T: Serialize,
+        V: DeserializeOwned,
+        F: FnOnce(Result<V, InvokeError>) + Send + 'static,
+    {
+        let args = match to_value(args) {
+            Ok(args) => args,
+            Err(err) => return on_response(Err(InvokeError::SerializeError(err))),
+        };
+        let engine = match self.engine() {
+            Some(engine) => engine,
+            None => return on_response(Err(EngineError::EngineDropped.into())),
+        };
+        let codec = self.codec;
+        let buf = codec.encode_method_call(&codec::MethodCall { method, args });
+        let name = self.name().to_owned();
+        // taken by the response handle, or by the failed send
+        let on_response = Arc::new(Mutex::new(Some(on_response)));
 
-            engine.send_platform_message(PlatformMessage {
-                channel: Cow::Borrowed(self.name()),
+        engine.run_on_platform_thread(move |engine| {
+            let callback = on_response.clone();
+            let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
+                if let Some(on_response) = callback.lock().take() {
+                    on_response(decode_method_response(codec, data));
+                }
+            });
+            if let Err(err) = engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(&name),
                 message: &buf,
                 response_handle: Some(handle),
-            });
-        }
+            }) {
+                error!("Failed to send message on channel {}: {}", name, err);
+                if let Some(on_response) = on_response.lock().take() {
+                    on_response(Err(err.into()));
+                }
+            }
+        });
+    }
+}
+
+fn decode_method_response<V>(codec: &dyn MethodCodec, data: &[u8]) -> Result<V, InvokeError>
+where
+    V: DeserializeOwned,
+{
+    // the engine responds with an empty message if dart has no handler for the channel
+    if data.is_empty() {
+        return Err(InvokeError::NoHandler);
+    }
+    match codec.decode_envelope(data) {
+        Some(MethodCallResult::Ok(value)) => Ok(from_value_owned(&value)?),
+        Some(MethodCallResult::Err {
+            code,
+            message,
+            details,
+        }) => Err(InvokeError::CustomError {
+            code,
+            message,
+            details,
+        }),
+        Some(MethodCallResult::NotImplemented) => Err(InvokeError::NotImplemented),
+        None => Err(InvokeError::InvalidResponse),
     }
 }
 
@@ -250,13 +435,59 @@ impl Channel for MethodChannel {
             call.args
         );
 
-        let call = MethodCall {
-            engine: self.engine.clone(),
-            codec,
-            inner: call,
-            response_handle: msg.response_handle,
-        };
+        let call = MethodCall::new(self.engine.clone(), codec, call, msg.response_handle);
 
         self.method_handler.borrow_mut().on_method_call(call);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::JSON_CODEC;
+    use std::collections::HashMap;
+
+    struct Handler;
+
+    impl MethodCallHandler for Handler {
+        fn on_method_call(&mut self, _call: MethodCall) {}
+    }
+
+    #[test]
+    fn test_invoke_with_unserializable_args() {
+        let channel = MethodChannel::new("test", Handler, &JSON_CODEC);
+        // maps with non-string keys can't be represented as a `Value`
+        let mut args = HashMap::new();
+        args.insert((1, 2), 3);
+        let result = channel
+            .invoke_method_async::<_, _, Value>("test", args)
+            .wait();
+        assert!(matches!(result, Err(InvokeError::SerializeError(_))));
+    }
+
+    #[test]
+    fn test_respond_without_handle() {
+        let call = MethodCall::new(
+            Default::default(),
+            &JSON_CODEC,
+            codec::MethodCall {
+                method: "test".into(),
+                args: Value::Null,
+            },
+            None,
+        );
+        assert!(!call.can_respond());
+        // must not panic
+        call.success_empty();
+    }
+
+    #[test]
+    fn test_invoke_without_engine() {
+        let channel = MethodChannel::new("test", Handler, &JSON_CODEC);
+        let result = channel.invoke_method_async::<_, _, Value>("test", 1).wait();
+        assert!(matches!(
+            result,
+            Err(InvokeError::Engine(EngineError::EngineDropped))
+        ));
+    }
+}
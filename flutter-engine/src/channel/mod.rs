@@ -5,19 +5,23 @@
 use crate::{FlutterEngine, FlutterEngineWeakRef};
 
 pub use self::{
+    event_channel::{EventChannel, EventHandler, EventSink},
     message_channel::{Message, MessageChannel, MessageHandler},
-    // event_channel::EventChannel,
-    method_channel::{MethodCall, MethodCallHandler, MethodChannel, MethodError},
+    method_channel::{
+        AsyncMethodCallHandler, MethodCall, MethodCallFuture, MethodCallHandler, MethodChannel,
+        MethodError,
+    },
     registry::ChannelRegistry,
+    response::ResponseFuture,
 };
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
 
+mod event_channel;
 mod message_channel;
-// TODO: Reimplement event channel support
-// mod event_channel;
 mod method_channel;
 pub mod platform_message;
 mod registry;
+mod response;
 
 pub trait Channel {
     fn name(&self) -> &str;
@@ -30,7 +34,13 @@ pub trait Channel {
     /// This method send a response to flutter. This is a low level method.
     fn send_response(&self, response_handle: PlatformMessageResponseHandle, buf: &[u8]) {
         if let Some(engine) = self.engine() {
-            engine.send_platform_message_response(response_handle, buf);
+            if let Err(err) = engine.send_platform_message_response(response_handle, buf) {
+                log::error!(
+                    "Failed to send response on channel {}: {}",
+                    self.name(),
+                    err
+                );
+            }
         } else {
             log::error!("Channel {} was not initialized", self.name());
         }
@@ -4,7 +4,8 @@ use std::{
     sync::{Arc, Weak},
 };
 
-use log::{trace, warn};
+use log::{error, trace, warn};
+use tracing::trace_span;
 
 use crate::FlutterEngineWeakRef;
 
@@ -52,6 +53,8 @@ impl ChannelRegistry {
     }
 
     pub fn handle(&self, mut message: PlatformMessage) {
+        let span = trace_span!("channel_message", channel = &*message.channel);
+        let _enter = span.enter();
         if let Some(channel) = self.channels.get(message.channel.deref()) {
             trace!("Processing message from channel: {}", message.channel);
             channel.handle_platform_message(message);
@@ -61,10 +64,14 @@ impl ChannelRegistry {
                 &message.channel
             );
             if let Some(handle) = message.response_handle.take() {
-                self.engine
+                if let Err(err) = self
+                    .engine
                     .upgrade()
                     .unwrap()
-                    .send_platform_message_response(handle, &[]);
+                    .send_platform_message_response(handle, &[])
+                {
+                    error!("Failed to send empty response: {}", err);
+                }
             }
         }
     }
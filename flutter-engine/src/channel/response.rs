@@ -0,0 +1,345 @@
+//! Futures resolving to the response of Dart to a message or method call.
+
+use crate::error::{EngineError, InvokeError};
+use parking_lot::{const_mutex, Condvar, Mutex};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct State<V> {
+    result: Option<Result<V, InvokeError>>,
+    waker: Option<Waker>,
+    completed: bool,
+}
+
+struct Shared<V> {
+    state: Mutex<State<V>>,
+    condvar: Condvar,
+}
+
+impl<V> Shared<V> {
+    /// Only the first result is kept, later ones, e.g. a response arriving after the timeout,
+    /// are dropped.
+    fn complete(&self, result: Result<V, InvokeError>) {
+        let waker = {
+            let mut state = self.state.lock();
+            if state.completed {
+                return;
+            }
+            state.completed = true;
+            state.result = Some(result);
+            state.waker.take()
+        };
+        self.condvar.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Responder<V> {
+    shared: Arc<Shared<V>>,
+}
+
+impl<V> Responder<V> {
+    pub(crate) fn complete(&self, result: Result<V, InvokeError>) {
+        self.shared.complete(result)
+    }
+}
+
+impl<V> Drop for Responder<V> {
+    fn drop(&mut self) {
+        // the message was dropped without being sent, e.g. because the engine went away
+        self.shared
+            .complete(Err(InvokeError::Engine(EngineError::EngineDropped)));
+    }
+}
+
+/// Resolves to the response of Dart. Without a timeout it waits until Dart responds, or forever
+/// if Dart never does.
+pub struct ResponseFuture<V> {
+    shared: Arc<Shared<V>>,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+pub(crate) fn response<V>() -> (Responder<V>, ResponseFuture<V>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            result: None,
+            waker: None,
+            completed: false,
+        }),
+        condvar: Condvar::new(),
+    });
+    (
+        Responder {
+            shared: shared.clone(),
+        },
+        ResponseFuture {
+            shared,
+            timeout: None,
+            deadline: None,
+        },
+    )
+}
+
+impl<V> ResponseFuture<V>
+where
+    V: Send + 'static,
+{
+    /// A response which already completed with `result`.
+    pub fn ready(result: Result<V, InvokeError>) -> Self {
+        let (responder, future) = response();
+        responder.complete(result);
+        future
+    }
+
+    /// Fail with `InvokeError::Timeout` if Dart doesn't respond within `timeout`. The timeout
+    /// starts when the future is first polled or waited on.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Block the current thread until Dart responded or the timeout elapsed.
+    ///
+    /// This must not be called on the platform thread, which has to deliver the response.
+    pub fn wait(self) -> Result<V, InvokeError> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.shared.state.lock();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if self
+                        .shared
+                        .condvar
+                        .wait_until(&mut state, deadline)
+                        .timed_out()
+                    {
+                        state.completed = true;
+                        return Err(InvokeError::Timeout);
+                    }
+                }
+                None => self.shared.condvar.wait(&mut state),
+            }
+        }
+    }
+
+    fn start_timer(&mut self) {
+        let timeout = match self.timeout {
+            Some(timeout) if self.deadline.is_none() => timeout,
+            _ => return,
+        };
+        let deadline = Instant::now() + timeout;
+        self.deadline = Some(deadline);
+        let shared: Arc<dyn Expire> = self.shared.clone();
+        timer().schedule(deadline, Arc::downgrade(&shared));
+    }
+}
+
+trait Expire: Send + Sync {
+    fn expire(&self);
+}
+
+impl<V: Send> Expire for Shared<V> {
+    fn expire(&self) {
+        self.complete(Err(InvokeError::Timeout));
+    }
+}
+
+struct Deadline {
+    time: Instant,
+    order: u64,
+    response: Weak<dyn Expire>,
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .cmp(&other.time)
+            .then_with(|| self.order.cmp(&other.order))
+    }
+}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Deadline {}
+
+#[derive(Default)]
+struct Deadlines {
+    heap: BinaryHeap<Reverse<Deadline>>,
+    next_order: u64,
+}
+
+/// Times out the responses of all futures on a single thread, which is started on first use.
+/// Futures which are dropped or completed before their deadline are skipped.
+struct Timer {
+    deadlines: Mutex<Deadlines>,
+    condvar: Condvar,
+}
+
+fn timer() -> Arc<Timer> {
+    static TIMER: Mutex<Option<Arc<Timer>>> = const_mutex(None);
+    TIMER.lock().get_or_insert_with(Timer::start).clone()
+}
+
+impl Timer {
+    fn start() -> Arc<Self> {
+        let timer = Arc::new(Self {
+            deadlines: Mutex::new(Default::default()),
+            condvar: Condvar::new(),
+        });
+        let thread_timer = timer.clone();
+        thread::Builder::new()
+            .name("flutter-response-timer".to_owned())
+            .spawn(move || thread_timer.run())
+            .expect("Failed to spawn response timer thread");
+        timer
+    }
+
+    fn schedule(&self, time: Instant, response: Weak<dyn Expire>) {
+        let mut deadlines = self.deadlines.lock();
+        let order = deadlines.next_order;
+        deadlines.next_order += 1;
+        deadlines.heap.push(Reverse(Deadline {
+            time,
+            order,
+            response,
+        }));
+        self.condvar.notify_one();
+    }
+
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(Reverse(deadline)) = deadlines.heap.peek() {
+                if deadline.time > now {
+                    break;
+                }
+                expired.push(deadlines.heap.pop().unwrap().0.response);
+            }
+            if !expired.is_empty() {
+                // completing a response wakes its task, which may schedule another timeout
+                drop(deadlines);
+                for response in expired.iter().filter_map(Weak::upgrade) {
+                    response.expire();
+                }
+                deadlines = self.deadlines.lock();
+                continue;
+            }
+            match deadlines.heap.peek() {
+                Some(Reverse(deadline)) => {
+                    let time = deadline.time;
+                    self.condvar.wait_until(&mut deadlines, time);
+                }
+                None => self.condvar.wait(&mut deadlines),
+            }
+        }
+    }
+}
+
+impl<V> Future for ResponseFuture<V>
+where
+    V: Send + 'static,
+{
+    type Output = Result<V, InvokeError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.start_timer();
+        let mut state = self.shared.state.lock();
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_task::noop_waker;
+
+    fn poll<V: Send + 'static>(future: &mut ResponseFuture<V>) -> Poll<Result<V, InvokeError>> {
+        let waker = noop_waker();
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn test_ready() {
+        let future = ResponseFuture::ready(Ok(1));
+        assert_eq!(future.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_wait_times_out() {
+        let (_responder, future) = response::<i32>();
+        let start = Instant::now();
+        let result = future.with_timeout(Duration::from_millis(10)).wait();
+        assert!(matches!(result, Err(InvokeError::Timeout)));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn test_poll_times_out() {
+        let (_responder, future) = response::<i32>();
+        let mut future = future.with_timeout(Duration::from_millis(10));
+        assert!(poll(&mut future).is_pending());
+        thread::sleep(Duration::from_millis(100));
+        assert!(matches!(
+            poll(&mut future),
+            Poll::Ready(Err(InvokeError::Timeout))
+        ));
+    }
+
+    #[test]
+    fn test_dropped_responder_completes_with_engine_dropped() {
+        let (responder, future) = response::<i32>();
+        drop(responder);
+        assert!(matches!(
+            future.wait(),
+            Err(InvokeError::Engine(EngineError::EngineDropped))
+        ));
+    }
+
+    #[test]
+    fn test_first_completion_wins() {
+        let (responder, future) = response();
+        responder.complete(Ok(1));
+        responder.complete(Ok(2));
+        drop(responder);
+        assert_eq!(future.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_response_before_timeout() {
+        let (responder, future) = response();
+        let mut future = future.with_timeout(Duration::from_millis(10));
+        assert!(poll(&mut future).is_pending());
+        responder.complete(Ok(1));
+        thread::sleep(Duration::from_millis(50));
+        assert!(matches!(poll(&mut future), Poll::Ready(Ok(1))));
+    }
+}
@@ -6,6 +6,15 @@ use serde::{de, ser, Deserialize, Serialize};
 
 pub use self::deserializer::{from_value, from_value_owned, Deserializer};
 
+/// Build a `Value` from JSON, for tests.
+#[cfg(test)]
+macro_rules! json_value {
+    ($($json:tt)+) => {
+        <$crate::codec::Value as std::convert::TryFrom<_>>::try_from(serde_json::json!($($json)+))
+            .unwrap()
+    };
+}
+
 mod deserializer;
 
 pub trait VecExt {
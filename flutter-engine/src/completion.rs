@@ -0,0 +1,119 @@
+//! Handles to observe the result of calls that may be deferred to the platform thread.
+
+use crate::error::EngineError;
+use parking_lot::{Condvar, Mutex};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+struct State<T, E> {
+    result: Option<Result<T, E>>,
+    waker: Option<Waker>,
+}
+
+struct Shared<T, E> {
+    state: Mutex<State<T, E>>,
+    condvar: Condvar,
+}
+
+impl<T, E> Shared<T, E> {
+    fn set(&self, result: Result<T, E>) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        self.condvar.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Completer<T, E: From<EngineError> = EngineError> {
+    shared: Option<Arc<Shared<T, E>>>,
+}
+
+impl<T, E: From<EngineError>> Completer<T, E> {
+    pub(crate) fn complete(mut self, result: Result<T, E>) {
+        if let Some(shared) = self.shared.take() {
+            shared.set(result);
+        }
+    }
+}
+
+impl<T, E: From<EngineError>> Drop for Completer<T, E> {
+    fn drop(&mut self) {
+        // the callback was dropped without being run, e.g. because the engine went away
+        if let Some(shared) = self.shared.take() {
+            shared.set(Err(EngineError::EngineDropped.into()));
+        }
+    }
+}
+
+/// Resolves once the call it was returned from has been executed on the platform thread.
+/// Calls made on the platform thread itself are executed immediately, so their handle is already
+/// complete.
+///
+/// The handle can either be awaited or waited on with `wait`.
+#[must_use = "the call may fail, use `wait` or await the handle to find out"]
+pub struct CompletionHandle<T, E = EngineError> {
+    shared: Arc<Shared<T, E>>,
+}
+
+pub(crate) fn completion<T, E: From<EngineError>>() -> (Completer<T, E>, CompletionHandle<T, E>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            result: None,
+            waker: None,
+        }),
+        condvar: Condvar::new(),
+    });
+    (
+        Completer {
+            shared: Some(shared.clone()),
+        },
+        CompletionHandle { shared },
+    )
+}
+
+impl<T, E: From<EngineError>> CompletionHandle<T, E> {
+    pub(crate) fn ready(result: Result<T, E>) -> Self {
+        let (completer, handle) = completion();
+        completer.complete(result);
+        handle
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.shared.state.lock().result.is_some()
+    }
+
+    /// Block the current thread until the call has completed.
+    ///
+    /// This must not be called on the platform thread for a handle that was returned on another
+    /// thread, as the call can only complete once the platform thread processes its tasks.
+    pub fn wait(self) -> Result<T, E> {
+        let mut state = self.shared.state.lock();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            self.shared.condvar.wait(&mut state);
+        }
+    }
+}
+
+impl<T, E> Future for CompletionHandle<T, E> {
+    type Output = Result<T, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock();
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -3,7 +3,8 @@ use std::{
     sync::mpsc::{RecvError, SendError},
 };
 
-use crate::codec::{MethodCallResult, Value};
+use crate::codec::{value, MethodCallResult, Value};
+use flutter_engine_sys::FlutterEngineResult;
 
 #[derive(Debug)]
 pub enum MethodArgsError {
@@ -225,3 +226,132 @@ impl serde::de::Error for ValueError {
 }
 
 impl error::Error for ValueError {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EngineError {
+    InvalidLibraryVersion,
+    InvalidArguments,
+    InternalInconsistency,
+    NotOnPlatformThread,
+    NotOnRenderThread,
+    EngineDropped,
+}
+
+impl EngineError {
+    pub(crate) fn check(result: FlutterEngineResult) -> Result<(), Self> {
+        match result {
+            FlutterEngineResult::kSuccess => Ok(()),
+            FlutterEngineResult::kInvalidLibraryVersion => Err(EngineError::InvalidLibraryVersion),
+            FlutterEngineResult::kInvalidArguments => Err(EngineError::InvalidArguments),
+            FlutterEngineResult::kInternalInconsistency => Err(EngineError::InternalInconsistency),
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::InvalidLibraryVersion => write!(f, "invalid engine library version"),
+            EngineError::InvalidArguments => write!(f, "invalid arguments"),
+            EngineError::InternalInconsistency => write!(f, "internal engine inconsistency"),
+            EngineError::NotOnPlatformThread => write!(f, "not on platform thread"),
+            EngineError::NotOnRenderThread => write!(f, "not on render thread"),
+            EngineError::EngineDropped => write!(f, "engine was dropped"),
+        }
+    }
+}
+
+impl error::Error for EngineError {}
+
+/// Errors of sending events through an `EventSink`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventError {
+    Engine(EngineError),
+    /// Dart cancelled the stream of the sink, or it was ended.
+    StreamClosed,
+    /// The event couldn't be converted to a `Value`, nothing was sent.
+    InvalidEvent,
+}
+
+impl From<EngineError> for EventError {
+    fn from(error: EngineError) -> Self {
+        EventError::Engine(error)
+    }
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventError::Engine(error) => write!(f, "{}", error),
+            EventError::StreamClosed => write!(f, "event stream was closed"),
+            EventError::InvalidEvent => write!(f, "event could not be serialized"),
+        }
+    }
+}
+
+impl error::Error for EventError {}
+
+/// Errors of calls into Dart which wait for a response.
+#[derive(Debug)]
+pub enum InvokeError {
+    Engine(EngineError),
+    /// No handler is registered for the channel on the Dart side.
+    NoHandler,
+    /// Dart didn't respond within the timeout.
+    Timeout,
+    /// The response couldn't be decoded by the codec of the channel.
+    InvalidResponse,
+    /// The arguments couldn't be converted to a `Value`, nothing was sent.
+    SerializeError(value::Error),
+    DeserializeError(ValueError),
+    NotImplemented,
+    CustomError {
+        code: String,
+        message: String,
+        details: Value,
+    },
+}
+
+impl From<EngineError> for InvokeError {
+    fn from(error: EngineError) -> Self {
+        InvokeError::Engine(error)
+    }
+}
+
+impl From<ValueError> for InvokeError {
+    fn from(error: ValueError) -> Self {
+        InvokeError::DeserializeError(error)
+    }
+}
+
+impl fmt::Display for InvokeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvokeError::Engine(err) => write!(f, "{}", err),
+            InvokeError::NoHandler => write!(f, "no handler registered in dart"),
+            InvokeError::Timeout => write!(f, "timed out waiting for a response"),
+            InvokeError::InvalidResponse => write!(f, "failed to decode response"),
+            InvokeError::SerializeError(err) => write!(f, "failed to serialize arguments: {}", err),
+            InvokeError::DeserializeError(err) => {
+                write!(f, "failed to deserialize response: {}", err)
+            }
+            InvokeError::NotImplemented => write!(f, "method not implemented"),
+            InvokeError::CustomError {
+                code,
+                message,
+                details,
+            } => write!(f, "{} ({})\ndetails: {:?}", message, code, details),
+        }
+    }
+}
+
+impl error::Error for InvokeError {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match self {
+            InvokeError::Engine(err) => Some(err),
+            InvokeError::SerializeError(err) => Some(err),
+            InvokeError::DeserializeError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! A single threaded executor for futures running on the platform thread.
+
+use crate::tasks::TaskRunnerHandler;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use futures_task::{waker, ArcWake, LocalFutureObj};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+
+struct TaskWaker {
+    id: usize,
+    ready: Sender<usize>,
+    handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        // the receiver lives as long as the engine, after that there is nothing to wake
+        if arc_self.ready.send(arc_self.id).is_ok() {
+            arc_self.handler.wake();
+        }
+    }
+}
+
+/// Futures are only polled from `execute_platform_tasks`, so they don't need to be `Send` and can
+/// use the engine directly. Wakers can be used from any thread, they wake the platform thread
+/// through its `TaskRunnerHandler`.
+pub(crate) struct LocalExecutor {
+    tasks: RefCell<HashMap<usize, LocalFutureObj<'static, ()>>>,
+    next_id: Cell<usize>,
+    ready_sender: Sender<usize>,
+    ready_receiver: Receiver<usize>,
+    handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+}
+
+impl LocalExecutor {
+    pub(crate) fn new(handler: Arc<dyn TaskRunnerHandler + Send + Sync>) -> Self {
+        let (ready_sender, ready_receiver) = unbounded();
+        Self {
+            tasks: RefCell::new(HashMap::new()),
+            next_id: Cell::new(0),
+            ready_sender,
+            ready_receiver,
+            handler,
+        }
+    }
+
+    /// Must only be called on the platform thread. The future is first polled during the next
+    /// call to `poll_ready`.
+    pub(crate) fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        self.tasks
+            .borrow_mut()
+            .insert(id, LocalFutureObj::new(Box::new(future)));
+        let _ = self.ready_sender.send(id);
+        self.handler.wake();
+    }
+
+    /// Poll all futures that were woken since the last call. Must only be called on the platform
+    /// thread.
+    pub(crate) fn poll_ready(&self) {
+        // futures woken while polling are polled in the next round, so a future waking itself
+        // can't starve the other tasks of the platform thread
+        let ready: Vec<usize> = self.ready_receiver.try_iter().collect();
+        for id in ready {
+            // the task is taken out while it is polled, so it can spawn other futures
+            let mut future = match self.tasks.borrow_mut().remove(&id) {
+                Some(future) => future,
+                // woken after it completed
+                None => continue,
+            };
+            let waker = waker(Arc::new(TaskWaker {
+                id,
+                ready: self.ready_sender.clone(),
+                handler: self.handler.clone(),
+            }));
+            let mut cx = Context::from_waker(&waker);
+            if Pin::new(&mut future).poll(&mut cx).is_pending() {
+                self.tasks.borrow_mut().insert(id, future);
+            }
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::ffi::{CString, NulError};
+use std::{mem, ptr};
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum FlutterPointerPhase {
     Cancel,
@@ -91,3 +94,119 @@ impl From<FlutterPointerMouseButtons> for flutter_engine_sys::FlutterPointerMous
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointerEvent {
+    pub device: i32,
+    pub phase: FlutterPointerPhase,
+    pub x: f64,
+    pub y: f64,
+    pub signal_kind: FlutterPointerSignalKind,
+    pub scroll_delta_x: f64,
+    pub scroll_delta_y: f64,
+    pub device_kind: FlutterPointerDeviceKind,
+    pub buttons: FlutterPointerMouseButtons,
+    /// Time the event occurred in microseconds, on the clock of `FlutterEngine::current_time`.
+    /// The framework computes the velocity of gestures from it.
+    pub timestamp: u64,
+}
+
+impl PointerEvent {
+    /// An event at (`x`, `y`) without scrolling, stamped with the current time.
+    pub fn new(
+        device: i32,
+        phase: FlutterPointerPhase,
+        (x, y): (f64, f64),
+        device_kind: FlutterPointerDeviceKind,
+        buttons: FlutterPointerMouseButtons,
+    ) -> Self {
+        Self {
+            device,
+            phase,
+            x,
+            y,
+            signal_kind: FlutterPointerSignalKind::None,
+            scroll_delta_x: 0.0,
+            scroll_delta_y: 0.0,
+            device_kind,
+            buttons,
+            timestamp: unsafe { flutter_engine_sys::FlutterEngineGetCurrentTime() } / 1000,
+        }
+    }
+}
+
+impl From<&PointerEvent> for flutter_engine_sys::FlutterPointerEvent {
+    fn from(event: &PointerEvent) -> Self {
+        let buttons: flutter_engine_sys::FlutterPointerMouseButtons = event.buttons.into();
+        Self {
+            struct_size: mem::size_of::<flutter_engine_sys::FlutterPointerEvent>(),
+            timestamp: event.timestamp as usize,
+            phase: event.phase.into(),
+            x: event.x,
+            y: event.y,
+            device: event.device,
+            signal_kind: event.signal_kind.into(),
+            scroll_delta_x: event.scroll_delta_x,
+            scroll_delta_y: event.scroll_delta_y,
+            device_kind: event.device_kind.into(),
+            buttons: buttons as i64,
+            #[cfg(all(target_arch = "arm", target_os = "android"))]
+            __bindgen_padding_0: 0,
+            #[cfg(all(target_arch = "arm", target_os = "android"))]
+            __bindgen_padding_1: 0,
+        }
+    }
+}
+
+/// A locale as understood by the framework, e.g. `zh_Hant_TW` has language `zh`, script `Hant`
+/// and country `TW`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Locale {
+    pub language: String,
+    pub country: Option<String>,
+    pub script: Option<String>,
+    pub variant: Option<String>,
+}
+
+impl Locale {
+    pub fn new(language: &str) -> Self {
+        Self {
+            language: language.to_owned(),
+            country: None,
+            script: None,
+            variant: None,
+        }
+    }
+}
+
+/// Owns the strings a `FlutterLocale` points to.
+pub(crate) struct LocaleStrings {
+    language: CString,
+    country: Option<CString>,
+    script: Option<CString>,
+    variant: Option<CString>,
+}
+
+impl LocaleStrings {
+    pub(crate) fn new(locale: &Locale) -> Result<Self, NulError> {
+        let optional = |value: &Option<String>| value.as_deref().map(CString::new).transpose();
+        Ok(Self {
+            language: CString::new(locale.language.as_str())?,
+            country: optional(&locale.country)?,
+            script: optional(&locale.script)?,
+            variant: optional(&locale.variant)?,
+        })
+    }
+
+    /// The returned struct is only valid as long as `self` is.
+    pub(crate) fn as_flutter_locale(&self) -> flutter_engine_sys::FlutterLocale {
+        let optional = |value: &Option<CString>| value.as_ref().map_or(ptr::null(), |s| s.as_ptr());
+        flutter_engine_sys::FlutterLocale {
+            struct_size: mem::size_of::<flutter_engine_sys::FlutterLocale>(),
+            language_code: self.language.as_ptr(),
+            country_code: optional(&self.country),
+            script_code: optional(&self.script),
+            variant_code: optional(&self.variant),
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::tasks::{TaskRunner, TaskRunnerInner};
-use crate::FlutterEngineInner;
+use crate::{FlutterEngineInner, RendererHandler};
 use log::trace;
 use parking_lot::Mutex;
 use std::os::raw::{c_char, c_uint, c_void};
@@ -8,7 +8,7 @@ pub extern "C" fn present(user_data: *mut c_void) -> bool {
     trace!("present");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.swap_buffers()
+        engine.opengl_handler().swap_buffers()
     }
 }
 
@@ -16,7 +16,7 @@ pub extern "C" fn make_current(user_data: *mut c_void) -> bool {
     trace!("make_current");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.make_current()
+        engine.opengl_handler().make_current()
     }
 }
 
@@ -24,7 +24,7 @@ pub extern "C" fn clear_current(user_data: *mut c_void) -> bool {
     trace!("clear_current");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.clear_current()
+        engine.opengl_handler().clear_current()
     }
 }
 
@@ -32,7 +32,7 @@ pub extern "C" fn fbo_callback(user_data: *mut c_void) -> c_uint {
     trace!("fbo_callback");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.fbo_callback()
+        engine.opengl_handler().fbo_callback()
     }
 }
 
@@ -40,7 +40,7 @@ pub extern "C" fn make_resource_current(user_data: *mut c_void) -> bool {
     trace!("make_resource_current");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.make_resource_current()
+        engine.opengl_handler().make_resource_current()
     }
 }
 
@@ -48,7 +48,28 @@ pub extern "C" fn gl_proc_resolver(user_data: *mut c_void, proc: *const c_char)
     trace!("gl_proc_resolver");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.opengl_handler.gl_proc_resolver(proc)
+        engine.opengl_handler().gl_proc_resolver(proc)
+    }
+}
+
+pub extern "C" fn software_present(
+    user_data: *mut c_void,
+    allocation: *const c_void,
+    row_bytes: usize,
+    height: usize,
+) -> bool {
+    trace!("software_present");
+    if allocation.is_null() {
+        return false;
+    }
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        if let RendererHandler::Software(handler) = &engine.renderer {
+            let buffer = std::slice::from_raw_parts(allocation as *const u8, row_bytes * height);
+            handler.present(buffer, row_bytes, height)
+        } else {
+            false
+        }
     }
 }
 
@@ -66,6 +87,40 @@ pub extern "C" fn platform_message_callback(
     }
 }
 
+pub extern "C" fn update_semantics_node_callback(
+    node: *const flutter_engine_sys::FlutterSemanticsNode,
+    user_data: *mut c_void,
+) {
+    trace!("update_semantics_node_callback");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let node = &*node;
+        let mut tree = engine.semantics_tree.write();
+        if node.id == flutter_engine_sys::kFlutterSemanticsNodeIdBatchEnd {
+            tree.commit_nodes();
+        } else {
+            tree.push_node(node.into());
+        }
+    }
+}
+
+pub extern "C" fn update_semantics_custom_action_callback(
+    action: *const flutter_engine_sys::FlutterSemanticsCustomAction,
+    user_data: *mut c_void,
+) {
+    trace!("update_semantics_custom_action_callback");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let action = &*action;
+        let mut tree = engine.semantics_tree.write();
+        if action.id == flutter_engine_sys::kFlutterSemanticsCustomActionIdBatchEnd {
+            tree.commit_custom_actions();
+        } else {
+            tree.push_custom_action(action.into());
+        }
+    }
+}
+
 pub extern "C" fn root_isolate_create_callback(_user_data: *mut c_void) {
     trace!("root_isolate_create_callback");
     // // This callback is executed on the main thread
@@ -77,6 +132,16 @@ pub extern "C" fn root_isolate_create_callback(_user_data: *mut c_void) {
     // }
 }
 
+pub extern "C" fn vsync_callback(user_data: *mut c_void, baton: isize) {
+    trace!("vsync_callback");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        if let Some(handler) = &engine.vsync_handler {
+            handler.request_frame_callback(engine.platform_runner.engine(), baton);
+        }
+    }
+}
+
 pub extern "C" fn runs_task_on_current_thread(user_data: *mut c_void) -> bool {
     trace!("runs_task_on_current_thread");
     unsafe {
@@ -0,0 +1,207 @@
+//! Engines without a window, e.g. to run Dart code for background jobs.
+
+use crate::builder::FlutterEngineBuilder;
+use crate::error::EngineError;
+use crate::plugins::{Plugin, PluginRegistrar};
+use crate::tasks::TaskRunnerHandler;
+use crate::{CreateError, FlutterEngine, FlutterSoftwareHandler, RendererHandler};
+use log::error;
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+/// Discards all frames.
+struct NoopSoftwareHandler;
+
+impl FlutterSoftwareHandler for NoopSoftwareHandler {
+    fn present(&self, _buffer: &[u8], _row_bytes: usize, _height: usize) -> bool {
+        true
+    }
+}
+
+/// Wakes the platform thread of a headless engine.
+#[derive(Default)]
+pub(crate) struct HeadlessTaskHandler {
+    woken: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl HeadlessTaskHandler {
+    pub(crate) fn wait(&self, until: Option<Instant>) {
+        let mut woken = self.woken.lock();
+        if !*woken {
+            match until {
+                Some(until) => {
+                    self.condvar.wait_until(&mut woken, until);
+                }
+                None => self.condvar.wait(&mut woken),
+            }
+        }
+        *woken = false;
+    }
+}
+
+impl TaskRunnerHandler for HeadlessTaskHandler {
+    fn wake(&self) {
+        *self.woken.lock() = true;
+        self.condvar.notify_one();
+    }
+}
+
+/// An engine running on its own platform thread, without rendering anything.
+///
+/// Channels and plugins work like they do for windowed engines, and any number of headless
+/// engines can run next to windowed ones.
+pub struct HeadlessEngine {
+    engine: FlutterEngine,
+    handler: Arc<HeadlessTaskHandler>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<(), EngineError>>>,
+    plugins: RwLock<PluginRegistrar>,
+}
+
+impl HeadlessEngine {
+    /// Start an engine with the assets, arguments and entrypoint of `builder`. Renderer, render
+    /// thread and platform handler set on the builder are ignored.
+    pub fn new(builder: FlutterEngineBuilder) -> Result<Self, CreateError> {
+        let handler = Arc::new(HeadlessTaskHandler::default());
+        let running = Arc::new(AtomicBool::new(true));
+        // only the parts used by a headless engine are moved to its platform thread, the handlers
+        // are replaced there
+        let FlutterEngineBuilder {
+            clock,
+            assets,
+            args,
+            switches,
+            entrypoint,
+            persistent_cache,
+            aot_data,
+            ..
+        } = builder;
+
+        let (engine_tx, engine_rx) = mpsc::channel();
+        let thread_handler = handler.clone();
+        let thread_running = running.clone();
+        let thread = thread::Builder::new()
+            .name("flutter-headless".to_owned())
+            .spawn(move || {
+                let builder = FlutterEngineBuilder {
+                    platform_handler: Some(thread_handler.clone()),
+                    render_handler: None,
+                    clock,
+                    renderer: Some(RendererHandler::Software(Box::new(NoopSoftwareHandler))),
+                    vsync_handler: None,
+                    assets,
+                    args,
+                    switches,
+                    entrypoint,
+                    persistent_cache,
+                    aot_data,
+                };
+                let engine = match builder.build() {
+                    Ok(engine) => engine,
+                    Err(err) => {
+                        let _ = engine_tx.send(Err(err));
+                        return Ok(());
+                    }
+                };
+                if let Err(err) = engine.run() {
+                    let _ = engine_tx.send(Err(CreateError::Engine(err)));
+                    return engine.shutdown();
+                }
+                let _ = engine_tx.send(Ok(engine.clone()));
+
+                while thread_running.load(Ordering::Acquire) {
+                    match engine.execute_platform_tasks() {
+                        Ok(next_task_time) => thread_handler.wait(next_task_time),
+                        Err(err) => {
+                            error!("Failed to execute platform tasks: {}", err);
+                            break;
+                        }
+                    }
+                }
+
+                if let Err(err) = engine.deinitialize() {
+                    error!("Failed to deinitialize headless engine: {}", err);
+                }
+                let result = engine.shutdown();
+                // this thread exits, so nothing else would shut down engines retired to it
+                FlutterEngine::shutdown_retired_engines();
+                result
+            })
+            .expect("Failed to spawn headless engine thread");
+
+        let engine = match engine_rx.recv() {
+            Ok(Ok(engine)) => engine,
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                return Err(err);
+            }
+            Err(_) => {
+                let _ = thread.join();
+                return Err(CreateError::Engine(EngineError::EngineDropped));
+            }
+        };
+
+        Ok(Self {
+            engine,
+            handler,
+            running,
+            thread: Some(thread),
+            plugins: RwLock::new(PluginRegistrar::new()),
+        })
+    }
+
+    pub fn engine(&self) -> FlutterEngine {
+        self.engine.clone()
+    }
+
+    pub fn add_plugin<P>(&self, plugin: P) -> &Self
+    where
+        P: Plugin + 'static,
+    {
+        self.plugins.write().add_plugin(&self.engine, plugin);
+        self
+    }
+
+    pub fn with_plugin<F, P>(&self, f: F)
+    where
+        F: FnOnce(&P),
+        P: Plugin + 'static,
+    {
+        self.plugins.read().with_plugin(f)
+    }
+
+    pub fn with_plugin_mut<F, P>(&self, f: F)
+    where
+        F: FnOnce(&mut P),
+        P: Plugin + 'static,
+    {
+        self.plugins.write().with_plugin_mut(f)
+    }
+
+    /// Stop the Dart isolate and shut down the engine, waiting for the platform thread to exit.
+    pub fn shutdown(mut self) -> Result<(), EngineError> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), EngineError> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        self.running.store(false, Ordering::Release);
+        self.handler.wake();
+        thread.join().unwrap_or(Err(EngineError::EngineDropped))
+    }
+}
+
+impl Drop for HeadlessEngine {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            error!("Failed to shut down headless engine: {}", err);
+        }
+    }
+}
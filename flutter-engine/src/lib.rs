@@ -1,38 +1,59 @@
+pub mod aot;
 pub mod builder;
+pub mod cache;
 pub mod channel;
 pub mod codec;
+pub mod completion;
 pub mod error;
+mod executor;
 pub mod ffi;
 mod flutter_callbacks;
+pub mod headless;
 pub mod plugins;
+pub mod semantics;
+pub mod switches;
 pub mod tasks;
-
-use futures_task::FutureObj;
+pub mod timeline;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 
 pub mod texture_registry;
 
+#[cfg(feature = "derive")]
+pub use flutter_engine_derive::method_channel;
+
+use crate::aot::{engine_runs_aot_compiled_dart_code, LoadedAotData};
 use crate::builder::FlutterEngineBuilder;
+use crate::cache::PersistentCache;
 use crate::channel::{Channel, ChannelRegistry};
+use crate::codec::{MessageCodec, Value, STANDARD_CODEC};
 use crate::ffi::{
     FlutterPointerDeviceKind, FlutterPointerMouseButtons, FlutterPointerPhase,
-    FlutterPointerSignalKind,
+    FlutterPointerSignalKind, Locale, LocaleStrings, PointerEvent,
 };
 
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
-use crate::tasks::TaskRunner;
+use crate::completion::{completion, CompletionHandle};
+use crate::error::EngineError;
+use crate::executor::LocalExecutor;
+use crate::semantics::{AccessibilityFeatures, SemanticsActions, SemanticsTree};
+use crate::switches::EngineSwitches;
+use crate::tasks::{TaskRunner, TaskRunnerHandler};
 use crate::texture_registry::{Texture, TextureRegistry};
-use async_std::task;
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use flutter_engine_sys::{FlutterEngineResult, FlutterTask};
-use log::trace;
-use parking_lot::RwLock;
+use flutter_engine_sys::FlutterTask;
+use log::{debug, error, trace, warn};
+use parking_lot::{const_mutex, Mutex, RwLock};
 use std::ffi::CString;
 use std::future::Future;
+use std::ops::Deref;
 use std::os::raw::{c_char, c_void};
 use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::{Arc, Weak};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-use std::{mem, ptr};
+use std::thread;
+use std::time::Instant;
 
 pub(crate) type MainThreadEngineFn = Box<dyn FnOnce(&FlutterEngine) + Send>;
 pub(crate) type MainThreadRenderThreadFn = Box<dyn FnOnce(&FlutterEngine) + Send>;
@@ -42,20 +63,136 @@ pub(crate) enum MainThreadCallback {
     RenderThread(MainThreadRenderThreadFn),
 }
 
+pub(crate) enum RendererHandler {
+    OpenGL(Box<dyn FlutterOpenGLHandler + Send>),
+    Software(Box<dyn FlutterSoftwareHandler + Send>),
+}
+
 struct FlutterEngineInner {
-    opengl_handler: Box<dyn FlutterOpenGLHandler + Send>,
-    engine_ptr: flutter_engine_sys::FlutterEngine,
+    renderer: RendererHandler,
+    // null once the engine was shut down
+    engine_ptr: AtomicPtr<flutter_engine_sys::_FlutterEngine>,
     channel_registry: RwLock<ChannelRegistry>,
     platform_runner: TaskRunner,
     platform_receiver: Receiver<MainThreadCallback>,
     platform_sender: Sender<MainThreadCallback>,
+    executor: LocalExecutor,
+    render_runner: Option<TaskRunner>,
+    render_receiver: Receiver<MainThreadRenderThreadFn>,
+    render_sender: Sender<MainThreadRenderThreadFn>,
     texture_registry: TextureRegistry,
+    semantics_tree: RwLock<SemanticsTree>,
+    vsync_handler: Option<Arc<dyn VsyncHandler + Send + Sync>>,
     assets: PathBuf,
     arguments: Vec<String>,
+    aot_data: Option<LoadedAotData>,
+    persistent_cache: Option<PersistentCache>,
+}
+
+impl FlutterEngineInner {
+    fn opengl_handler(&self) -> &dyn FlutterOpenGLHandler {
+        match &self.renderer {
+            RendererHandler::OpenGL(handler) => &**handler,
+            RendererHandler::Software(_) => panic!("Engine was not configured to use opengl"),
+        }
+    }
+}
+
+impl Drop for FlutterEngineInner {
+    fn drop(&mut self) {
+        let engine_ptr = self.engine_ptr.swap(ptr::null_mut(), Ordering::AcqRel);
+        if engine_ptr.is_null() {
+            return;
+        }
+        // `EngineHandle::drop` retires running engines instead of dropping them on another thread
+        debug_assert!(self.platform_runner.runs_task_on_current_thread());
+        // callbacks still reach the fields of the engine while it is shut down, they are only
+        // dropped after this returns
+        unsafe {
+            if let Err(err) =
+                EngineError::check(flutter_engine_sys::FlutterEngineDeinitialize(engine_ptr))
+            {
+                debug!("Failed to deinitialize engine: {}", err);
+            }
+            if let Err(err) =
+                EngineError::check(flutter_engine_sys::FlutterEngineShutdown(engine_ptr))
+            {
+                error!("Failed to shut down engine: {}", err);
+            }
+        }
+    }
+}
+
+/// Engines whose last handle was dropped outside of their platform thread. The callbacks of a
+/// running engine point into it, so it is kept alive until the next time tasks are executed on
+/// its platform thread, where it is shut down, or until the embedder tears down its loop and calls
+/// `FlutterEngine::shutdown_retired_engines`.
+static RETIRED_ENGINES: Mutex<Vec<RetiredEngine>> = const_mutex(Vec::new());
+
+struct RetiredEngine(Arc<FlutterEngineInner>);
+
+unsafe impl Send for RetiredEngine {}
+
+fn shutdown_retired_engines() {
+    let engines: Vec<RetiredEngine> = {
+        let mut retired = RETIRED_ENGINES.lock();
+        let (current, other) = retired
+            .drain(..)
+            .partition(|engine| engine.0.platform_runner.runs_task_on_current_thread());
+        *retired = other;
+        current
+    };
+    if !engines.is_empty() {
+        debug!("Shutting down {} retired engines", engines.len());
+    }
+    // the lock is released before, shutting down an engine executes its callbacks
+    drop(engines);
+}
+
+/// Shared by all handles of an engine, dropped once the last one is gone.
+struct EngineHandle {
+    inner: Arc<FlutterEngineInner>,
+}
+
+unsafe impl Send for EngineHandle {}
+
+unsafe impl Sync for EngineHandle {}
+
+impl Deref for EngineHandle {
+    type Target = FlutterEngineInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        // The engine can only be shut down on the platform thread, a running engine is handed
+        // over to it instead of being dropped here.
+        if self.engine_ptr.load(Ordering::Acquire).is_null()
+            || self.platform_runner.runs_task_on_current_thread()
+        {
+            return;
+        }
+        debug!("Last handle of a running engine dropped outside of the platform thread");
+        RETIRED_ENGINES
+            .lock()
+            .push(RetiredEngine(self.inner.clone()));
+        self.platform_runner.wake();
+    }
+}
+
+/// Posts callbacks to the platform thread of an engine without keeping the engine alive.
+#[derive(Clone)]
+struct PlatformQueue {
+    sender: Sender<MainThreadCallback>,
+    handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
 }
 
 pub struct FlutterEngineWeakRef {
-    inner: Weak<FlutterEngineInner>,
+    inner: Weak<EngineHandle>,
+    platform: Option<PlatformQueue>,
 }
 
 unsafe impl Send for FlutterEngineWeakRef {}
@@ -71,17 +208,42 @@ impl FlutterEngineWeakRef {
     }
 
     pub fn is_valid(&self) -> bool {
-        self.upgrade().is_some()
+        self.inner.strong_count() > 0
     }
 
     pub fn ptr_equal(&self, other: Self) -> bool {
         self.inner.ptr_eq(&other.inner)
     }
+
+    /// Like `FlutterEngine::run_on_platform_thread`, but can be called from any thread without
+    /// creating a handle of the engine there. `f` is always called later on the platform thread.
+    /// Returns false if the engine was already dropped, `f` is not called then.
+    pub fn run_on_platform_thread<F>(&self, f: F) -> bool
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        let platform = match &self.platform {
+            Some(platform) => platform,
+            None => return false,
+        };
+        if platform
+            .sender
+            .send(MainThreadCallback::Engine(Box::new(f)))
+            .is_err()
+        {
+            return false;
+        }
+        platform.handler.wake();
+        true
+    }
 }
 
 impl Default for FlutterEngineWeakRef {
     fn default() -> Self {
-        Self { inner: Weak::new() }
+        Self {
+            inner: Weak::new(),
+            platform: None,
+        }
     }
 }
 
@@ -89,12 +251,13 @@ impl Clone for FlutterEngineWeakRef {
     fn clone(&self) -> Self {
         Self {
             inner: Weak::clone(&self.inner),
+            platform: self.platform.clone(),
         }
     }
 }
 
 pub struct FlutterEngine {
-    inner: Arc<FlutterEngineInner>,
+    inner: Arc<EngineHandle>,
 }
 
 unsafe impl Send for FlutterEngine {}
@@ -123,72 +286,142 @@ pub trait FlutterOpenGLHandler {
     fn gl_proc_resolver(&self, proc: *const c_char) -> *mut c_void;
 }
 
+pub trait FlutterSoftwareHandler {
+    /// Called with a fully rendered frame in Skia's native 32-bit format (N32), which is BGRA on
+    /// little endian Linux, Windows and macOS. The buffer contains `height` rows of `row_bytes`
+    /// bytes each and is owned by the engine, so it has to be copied if it is needed after this
+    /// call returns.
+    fn present(&self, buffer: &[u8], row_bytes: usize, height: usize) -> bool;
+}
+
+pub trait VsyncHandler {
+    /// Called on an engine managed thread whenever the engine waits for the next vsync. The baton
+    /// has to be handed back through `FlutterEngine::on_vsync`, every baton must be returned
+    /// before the engine is shut down.
+    fn request_frame_callback(&self, engine: FlutterEngineWeakRef, baton: isize);
+}
+
 impl FlutterEngine {
     pub(crate) fn new(builder: FlutterEngineBuilder) -> Result<Self, CreateError> {
-        // Convert arguments into flutter compatible
-        let mut args = Vec::with_capacity(builder.args.len() + 2);
-        args.push(CString::new("flutter-rs").unwrap().into_raw());
-        args.push(
-            CString::new("--icu-symbol-prefix=gIcudtl")
-                .unwrap()
-                .into_raw(),
-        );
-        for arg in builder.args.iter() {
-            args.push(CString::new(arg.as_str()).unwrap().into_raw());
+        // Convert arguments into flutter compatible. The engine copies all strings during
+        // initialization, so they only have to live until then.
+        let mut switches = builder.switches.clone();
+        switches.merge(EngineSwitches::from_env());
+        let mut arguments = switches.to_args();
+        arguments.extend(builder.args);
+        let mut c_args = Vec::with_capacity(arguments.len() + 2);
+        c_args.push(CString::new("flutter-rs").unwrap());
+        c_args.push(CString::new("--icu-symbol-prefix=gIcudtl").unwrap());
+        for arg in arguments.iter() {
+            c_args.push(
+                CString::new(arg.as_str())
+                    .map_err(|_| CreateError::Engine(EngineError::InvalidArguments))?,
+            );
         }
+        let mut args: Vec<*const c_char> = c_args.iter().map(|arg| arg.as_ptr()).collect();
+
+        let entrypoint = match &builder.entrypoint {
+            Some(entrypoint) => Some(
+                CString::new(entrypoint.as_str())
+                    .map_err(|_| CreateError::Engine(EngineError::InvalidArguments))?,
+            ),
+            None => None,
+        };
+
+        let renderer = builder.renderer.ok_or(CreateError::NoHandler)?;
+        let platform_handler = builder.platform_handler.ok_or(CreateError::NoHandler)?;
+        let clock = builder.clock;
+
+        let engine_runs_aot = engine_runs_aot_compiled_dart_code();
+        if engine_runs_aot != builder.aot_data.is_some() {
+            return Err(CreateError::AotMismatch { engine_runs_aot });
+        }
+        let aot_data = match &builder.aot_data {
+            Some(data) => Some(
+                LoadedAotData::load(data).map_err(|err| CreateError::AotData(err.to_string()))?,
+            ),
+            None => None,
+        };
 
         let (main_tx, main_rx) = unbounded();
+        let (render_tx, render_rx) = unbounded();
 
         let engine = Self {
-            inner: Arc::new(FlutterEngineInner {
-                opengl_handler: builder
-                    .opengl_handler
-                    .expect("Only opengl is supported (for now)"),
-                engine_ptr: ptr::null_mut(),
-                channel_registry: RwLock::new(ChannelRegistry::new()),
-                platform_runner: TaskRunner::new(
-                    builder.platform_handler.expect("No platform runner set"),
-                ),
-                platform_receiver: main_rx,
-                platform_sender: main_tx,
-                texture_registry: TextureRegistry::new(),
-                assets: builder.assets,
-                arguments: builder.args,
+            inner: Arc::new(EngineHandle {
+                inner: Arc::new(FlutterEngineInner {
+                    renderer,
+                    engine_ptr: AtomicPtr::new(ptr::null_mut()),
+                    channel_registry: RwLock::new(ChannelRegistry::new()),
+                    platform_runner: TaskRunner::with_clock(
+                        platform_handler.clone(),
+                        thread::current().id(),
+                        clock.clone(),
+                    ),
+                    platform_receiver: main_rx,
+                    platform_sender: main_tx,
+                    executor: LocalExecutor::new(platform_handler),
+                    render_runner: builder.render_handler.map(|(handler, thread_id)| {
+                        TaskRunner::with_clock(handler, thread_id, clock.clone())
+                    }),
+                    render_receiver: render_rx,
+                    render_sender: render_tx,
+                    texture_registry: TextureRegistry::new(),
+                    semantics_tree: RwLock::new(SemanticsTree::new()),
+                    vsync_handler: builder.vsync_handler,
+                    assets: builder.assets,
+                    arguments,
+                    aot_data,
+                    persistent_cache: builder.persistent_cache,
+                }),
             }),
         };
 
         let inner = &engine.inner;
         inner.channel_registry.write().init(engine.downgrade());
         inner.platform_runner.init(engine.downgrade());
+        if let Some(render_runner) = &inner.render_runner {
+            render_runner.init(engine.downgrade());
+        }
 
         // Configure renderer
-        let renderer_config = flutter_engine_sys::FlutterRendererConfig {
-            type_: flutter_engine_sys::FlutterRendererType::kOpenGL,
-            __bindgen_anon_1: flutter_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
-                open_gl: flutter_engine_sys::FlutterOpenGLRendererConfig {
-                    struct_size: std::mem::size_of::<flutter_engine_sys::FlutterOpenGLRendererConfig>(
-                    ),
-                    make_current: Some(flutter_callbacks::make_current),
-                    clear_current: Some(flutter_callbacks::clear_current),
-                    present: Some(flutter_callbacks::present),
-                    fbo_callback: Some(flutter_callbacks::fbo_callback),
-                    make_resource_current: Some(flutter_callbacks::make_resource_current),
-                    fbo_reset_after_present: false,
-                    surface_transformation: None,
-                    gl_proc_resolver: Some(flutter_callbacks::gl_proc_resolver),
-                    gl_external_texture_frame_callback: Some(
-                        flutter_callbacks::gl_external_texture_frame,
-                    ),
+        let renderer_config = match inner.renderer {
+            RendererHandler::OpenGL(_) => flutter_engine_sys::FlutterRendererConfig {
+                type_: flutter_engine_sys::FlutterRendererType::kOpenGL,
+                __bindgen_anon_1: flutter_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
+                    open_gl: flutter_engine_sys::FlutterOpenGLRendererConfig {
+                        struct_size: std::mem::size_of::<
+                            flutter_engine_sys::FlutterOpenGLRendererConfig,
+                        >(),
+                        make_current: Some(flutter_callbacks::make_current),
+                        clear_current: Some(flutter_callbacks::clear_current),
+                        present: Some(flutter_callbacks::present),
+                        fbo_callback: Some(flutter_callbacks::fbo_callback),
+                        make_resource_current: Some(flutter_callbacks::make_resource_current),
+                        fbo_reset_after_present: false,
+                        surface_transformation: None,
+                        gl_proc_resolver: Some(flutter_callbacks::gl_proc_resolver),
+                        gl_external_texture_frame_callback: Some(
+                            flutter_callbacks::gl_external_texture_frame,
+                        ),
+                    },
+                },
+            },
+            RendererHandler::Software(_) => flutter_engine_sys::FlutterRendererConfig {
+                type_: flutter_engine_sys::FlutterRendererType::kSoftware,
+                __bindgen_anon_1: flutter_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
+                    software: flutter_engine_sys::FlutterSoftwareRendererConfig {
+                        struct_size: std::mem::size_of::<
+                            flutter_engine_sys::FlutterSoftwareRendererConfig,
+                        >(),
+                        surface_present_callback: Some(flutter_callbacks::software_present),
+                    },
                 },
             },
         };
 
-        // Configure engine threads
-        // TODO: Should be downgraded to a weak once weak::into_raw lands in stable
-        let runner_ptr = {
-            let arc = inner.platform_runner.clone().inner;
-            Arc::into_raw(arc) as *mut std::ffi::c_void
-        };
+        // Configure engine threads. The runners are owned by the engine, which is shut down
+        // before they are dropped.
+        let runner_ptr = &*inner.platform_runner.inner as *const _ as *mut std::ffi::c_void;
 
         let platform_task_runner = flutter_engine_sys::FlutterTaskRunnerDescription {
             struct_size: std::mem::size_of::<flutter_engine_sys::FlutterTaskRunnerDescription>(),
@@ -199,69 +432,117 @@ impl FlutterEngine {
             post_task_callback: Some(flutter_callbacks::post_task),
             identifier: 0,
         };
+
+        let render_task_runner = inner.render_runner.as_ref().map(|render_runner| {
+            let runner_ptr = &*render_runner.inner as *const _ as *mut std::ffi::c_void;
+
+            flutter_engine_sys::FlutterTaskRunnerDescription {
+                struct_size: std::mem::size_of::<flutter_engine_sys::FlutterTaskRunnerDescription>(
+                ),
+                user_data: runner_ptr,
+                runs_task_on_current_thread_callback: Some(
+                    flutter_callbacks::runs_task_on_current_thread,
+                ),
+                post_task_callback: Some(flutter_callbacks::post_task),
+                identifier: 1,
+            }
+        });
+
         let custom_task_runners = flutter_engine_sys::FlutterCustomTaskRunners {
             struct_size: std::mem::size_of::<flutter_engine_sys::FlutterCustomTaskRunners>(),
             platform_task_runner: &platform_task_runner
                 as *const flutter_engine_sys::FlutterTaskRunnerDescription,
-            render_task_runner: std::ptr::null(),
+            render_task_runner: render_task_runner
+                .as_ref()
+                .map_or(std::ptr::null(), |runner| {
+                    runner as *const flutter_engine_sys::FlutterTaskRunnerDescription
+                }),
         };
 
         // Configure engine
+        let persistent_cache_path = inner.persistent_cache.as_ref().map(|cache| {
+            if !cache.is_read_only() {
+                if let Err(err) = std::fs::create_dir_all(cache.path()) {
+                    warn!("Failed to create persistent cache directory: {}", err);
+                }
+            }
+            path_to_cstring(cache.path())
+        });
+        let assets_path = path_to_cstring(&inner.assets);
+        let is_persistent_cache_read_only = match &inner.persistent_cache {
+            Some(cache) => cache.is_read_only(),
+            None => false,
+        };
+        let snapshots = inner
+            .aot_data
+            .as_ref()
+            .map_or([(ptr::null(), 0); 4], LoadedAotData::snapshots);
         let project_args = flutter_engine_sys::FlutterProjectArgs {
             struct_size: std::mem::size_of::<flutter_engine_sys::FlutterProjectArgs>(),
-            assets_path: path_to_cstring(&inner.assets).into_raw(),
+            assets_path: assets_path.as_ptr(),
             main_path__unused__: std::ptr::null(),
             packages_path__unused__: std::ptr::null(),
             icu_data_path: std::ptr::null(),
             command_line_argc: args.len() as i32,
             command_line_argv: args.as_mut_ptr() as _,
             platform_message_callback: Some(flutter_callbacks::platform_message_callback),
-            vm_snapshot_data: std::ptr::null(),
-            vm_snapshot_data_size: 0,
-            vm_snapshot_instructions: std::ptr::null(),
-            vm_snapshot_instructions_size: 0,
-            isolate_snapshot_data: std::ptr::null(),
-            isolate_snapshot_data_size: 0,
-            isolate_snapshot_instructions: std::ptr::null(),
-            isolate_snapshot_instructions_size: 0,
+            vm_snapshot_data: snapshots[0].0,
+            vm_snapshot_data_size: snapshots[0].1,
+            vm_snapshot_instructions: snapshots[1].0,
+            vm_snapshot_instructions_size: snapshots[1].1,
+            isolate_snapshot_data: snapshots[2].0,
+            isolate_snapshot_data_size: snapshots[2].1,
+            isolate_snapshot_instructions: snapshots[3].0,
+            isolate_snapshot_instructions_size: snapshots[3].1,
             root_isolate_create_callback: Some(flutter_callbacks::root_isolate_create_callback),
-            update_semantics_node_callback: None,
-            update_semantics_custom_action_callback: None,
-            persistent_cache_path: std::ptr::null(),
-            is_persistent_cache_read_only: false,
-            vsync_callback: None,
-            custom_dart_entrypoint: std::ptr::null(),
+            update_semantics_node_callback: Some(flutter_callbacks::update_semantics_node_callback),
+            update_semantics_custom_action_callback: Some(
+                flutter_callbacks::update_semantics_custom_action_callback,
+            ),
+            persistent_cache_path: persistent_cache_path
+                .as_ref()
+                .map_or(std::ptr::null(), |path| path.as_ptr()),
+            is_persistent_cache_read_only,
+            vsync_callback: if inner.vsync_handler.is_some() {
+                Some(flutter_callbacks::vsync_callback)
+            } else {
+                None
+            },
+            custom_dart_entrypoint: entrypoint
+                .as_ref()
+                .map_or(std::ptr::null(), |entrypoint| entrypoint.as_ptr()),
             custom_task_runners: &custom_task_runners
                 as *const flutter_engine_sys::FlutterCustomTaskRunners,
-            shutdown_dart_vm_when_done: true,
+            // keep the VM alive, so another engine can be started after this one is shut down
+            shutdown_dart_vm_when_done: false,
             compositor: std::ptr::null(),
         };
 
-        // Initialise engine
+        // Initialise engine. The callbacks get a pointer to the inner engine without owning it,
+        // it is valid until the engine is shut down when the inner engine is dropped.
+        let inner_ptr = &*inner.inner as *const FlutterEngineInner as *mut std::ffi::c_void;
+        let mut engine_ptr = ptr::null_mut();
         unsafe {
-            // TODO: Should be downgraded to a weak once weak::into_raw lands in stable
-            let inner_ptr = Arc::into_raw(inner.clone()) as *mut std::ffi::c_void;
-
-            if flutter_engine_sys::FlutterEngineInitialize(
+            EngineError::check(flutter_engine_sys::FlutterEngineInitialize(
                 1,
                 &renderer_config,
                 &project_args,
                 inner_ptr,
-                &inner.engine_ptr as *const flutter_engine_sys::FlutterEngine
-                    as *mut flutter_engine_sys::FlutterEngine,
-            ) != flutter_engine_sys::FlutterEngineResult::kSuccess
-                || inner.engine_ptr.is_null()
-            {
-                Err(CreateError::EnginePtrNull)
-            } else {
-                Ok(engine)
-            }
+                &mut engine_ptr,
+            ))
+            .map_err(CreateError::Engine)?;
         }
+        if engine_ptr.is_null() {
+            return Err(CreateError::EnginePtrNull);
+        }
+        inner.engine_ptr.store(engine_ptr, Ordering::Release);
+        Ok(engine)
     }
 
+    /// Null after the engine was shut down.
     #[inline]
     pub fn engine_ptr(&self) -> flutter_engine_sys::FlutterEngine {
-        self.inner.engine_ptr
+        self.inner.engine_ptr.load(Ordering::Acquire)
     }
 
     pub fn register_channel<C>(&self, channel: C) -> Weak<C>
@@ -294,6 +575,10 @@ impl FlutterEngine {
     pub fn downgrade(&self) -> FlutterEngineWeakRef {
         FlutterEngineWeakRef {
             inner: Arc::downgrade(&self.inner),
+            platform: Some(PlatformQueue {
+                sender: self.inner.platform_sender.clone(),
+                handler: self.inner.platform_runner.handler(),
+            }),
         }
     }
 
@@ -301,21 +586,24 @@ impl FlutterEngine {
         &self.inner.assets
     }
 
+    pub fn persistent_cache(&self) -> Option<&PersistentCache> {
+        self.inner.persistent_cache.as_ref()
+    }
+
+    /// The switches and arguments the engine was started with.
     pub fn arguments(&self) -> &Vec<String> {
         &self.inner.arguments
     }
 
-    pub fn run(&self) -> Result<(), ()> {
+    pub fn run(&self) -> Result<(), EngineError> {
         if !self.is_platform_thread() {
-            panic!("Not on platform thread");
+            return Err(EngineError::NotOnPlatformThread);
         }
 
-        // TODO: Safeguard, process results
         unsafe {
-            match flutter_engine_sys::FlutterEngineRunInitialized(self.engine_ptr()) {
-                FlutterEngineResult::kSuccess => Ok(()),
-                _ => Err(()),
-            }
+            EngineError::check(flutter_engine_sys::FlutterEngineRunInitialized(
+                self.engine_ptr(),
+            ))
         }
     }
 
@@ -329,6 +617,15 @@ impl FlutterEngine {
         self.inner.platform_runner.runs_task_on_current_thread()
     }
 
+    /// Returns false if no render thread was configured and the engine manages it on its own.
+    #[inline]
+    pub fn is_render_thread(&self) -> bool {
+        match &self.inner.render_runner {
+            Some(render_runner) => render_runner.runs_task_on_current_thread(),
+            None => false,
+        }
+    }
+
     pub fn run_on_platform_thread<F>(&self, f: F)
     where
         F: FnOnce(&FlutterEngine) -> () + 'static + Send,
@@ -340,28 +637,72 @@ impl FlutterEngine {
         }
     }
 
+    /// Like `run_on_platform_thread`, but returns a handle which resolves to the result of `f`.
+    pub(crate) fn run_on_platform_thread_with_completion<T, F>(&self, f: F) -> CompletionHandle<T>
+    where
+        F: FnOnce(&FlutterEngine) -> Result<T, EngineError> + 'static + Send,
+        T: Send + 'static,
+    {
+        if self.is_platform_thread() {
+            CompletionHandle::ready(f(self))
+        } else {
+            let (completer, handle) = completion();
+            self.post_platform_callback(MainThreadCallback::Engine(Box::new(move |engine| {
+                completer.complete(f(engine))
+            })));
+            handle
+        }
+    }
+
+    /// Run a future on the platform thread, where it can use the engine directly. The future is
+    /// polled from `execute_platform_tasks`, so it must not block.
+    ///
+    /// The returned handle resolves to the output of the future, or to an error if this is not
+    /// called on the platform thread or the engine is dropped before the future completes.
+    pub fn spawn_local<T, F>(&self, future: F) -> CompletionHandle<T>
+    where
+        F: Future<Output = T> + 'static,
+        T: Send + 'static,
+    {
+        if !self.is_platform_thread() {
+            return CompletionHandle::ready(Err(EngineError::NotOnPlatformThread));
+        }
+        let (completer, handle) = completion();
+        self.inner.executor.spawn(async move {
+            completer.complete(Ok(future.await));
+        });
+        handle
+    }
+
     pub fn run_on_render_thread<F>(&self, f: F)
     where
         F: FnOnce(&FlutterEngine) -> () + 'static + Send,
     {
-        // TODO: Reimplement render thread
-        // if self.is_platform_thread() {
-        //     f(self);
-        // } else {
-        self.post_platform_callback(MainThreadCallback::RenderThread(Box::new(f)));
-        // }
+        if let Some(render_runner) = &self.inner.render_runner {
+            if render_runner.runs_task_on_current_thread() {
+                f(self);
+            } else {
+                self.inner.render_sender.send(Box::new(f)).unwrap();
+                render_runner.wake();
+            }
+        } else {
+            self.post_platform_callback(MainThreadCallback::RenderThread(Box::new(f)));
+        }
     }
 
-    #[deprecated(note = "Soon to be removed: Unclear use cases")]
+    #[cfg(feature = "async-std")]
+    #[deprecated(note = "Use spawn_local to run futures on the platform thread")]
     pub fn run_in_background(&self, future: impl Future<Output = ()> + Send + 'static) {
-        task::spawn(FutureObj::new(Box::new(future)));
+        async_std::task::spawn(futures_task::FutureObj::new(Box::new(future)));
     }
 
-    pub fn send_window_metrics_event(&self, width: usize, height: usize, pixel_ratio: f64) {
-        if !self.is_platform_thread() {
-            panic!("Not on platform thread");
-        }
-
+    /// Can be called from any thread, the event is sent on the platform thread.
+    pub fn send_window_metrics_event(
+        &self,
+        width: usize,
+        height: usize,
+        pixel_ratio: f64,
+    ) -> CompletionHandle<()> {
         let event = flutter_engine_sys::FlutterWindowMetricsEvent {
             struct_size: std::mem::size_of::<flutter_engine_sys::FlutterWindowMetricsEvent>(),
             width,
@@ -370,61 +711,189 @@ impl FlutterEngine {
             #[cfg(all(target_arch = "arm", target_os = "android"))]
             __bindgen_padding_0: 0,
         };
-        unsafe {
-            flutter_engine_sys::FlutterEngineSendWindowMetricsEvent(self.engine_ptr(), &event);
-        }
+        self.run_on_platform_thread_with_completion(move |engine| unsafe {
+            EngineError::check(flutter_engine_sys::FlutterEngineSendWindowMetricsEvent(
+                engine.engine_ptr(),
+                &event,
+            ))
+        })
     }
 
+    /// Can be called from any thread, the event is sent on the platform thread.
+    /// The timestamp of the event is taken when this is called.
     #[allow(clippy::too_many_arguments)]
     pub fn send_pointer_event(
         &self,
         device: i32,
         phase: FlutterPointerPhase,
-        (x, y): (f64, f64),
+        position: (f64, f64),
         signal_kind: FlutterPointerSignalKind,
         (scroll_delta_x, scroll_delta_y): (f64, f64),
         device_kind: FlutterPointerDeviceKind,
         buttons: FlutterPointerMouseButtons,
-    ) {
-        if !self.is_platform_thread() {
-            panic!("Not on platform thread");
-        }
-
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let buttons: flutter_engine_sys::FlutterPointerMouseButtons = buttons.into();
-        let event = flutter_engine_sys::FlutterPointerEvent {
-            struct_size: mem::size_of::<flutter_engine_sys::FlutterPointerEvent>(),
-            timestamp: timestamp.as_micros() as usize,
-            phase: phase.into(),
-            x,
-            y,
-            device,
-            signal_kind: signal_kind.into(),
+    ) -> CompletionHandle<()> {
+        let event = PointerEvent {
+            signal_kind,
             scroll_delta_x,
             scroll_delta_y,
-            device_kind: device_kind.into(),
-            buttons: buttons as i64,
-            #[cfg(all(target_arch = "arm", target_os = "android"))]
-            __bindgen_padding_0: 0,
-            #[cfg(all(target_arch = "arm", target_os = "android"))]
-            __bindgen_padding_1: 0,
+            ..PointerEvent::new(device, phase, position, device_kind, buttons)
         };
-        unsafe {
-            flutter_engine_sys::FlutterEngineSendPointerEvent(self.engine_ptr(), &event, 1);
+        self.send_pointer_events(&[event])
+    }
+
+    /// Send multiple events in one batch, e.g. all events received since the last frame. The
+    /// events keep their timestamps, which should be the time the OS received them.
+    ///
+    /// Can be called from any thread, the events are sent on the platform thread.
+    pub fn send_pointer_events(&self, events: &[PointerEvent]) -> CompletionHandle<()> {
+        let events: Vec<flutter_engine_sys::FlutterPointerEvent> =
+            events.iter().map(Into::into).collect();
+        self.run_on_platform_thread_with_completion(move |engine| {
+            if events.is_empty() {
+                return Ok(());
+            }
+            unsafe {
+                EngineError::check(flutter_engine_sys::FlutterEngineSendPointerEvent(
+                    engine.engine_ptr(),
+                    events.as_ptr(),
+                    events.len(),
+                ))
+            }
+        })
+    }
+
+    /// Send the preferred locales of the user, most preferred first.
+    ///
+    /// Can be called from any thread, the locales are sent on the platform thread.
+    pub fn update_locales(&self, locales: &[Locale]) -> CompletionHandle<()> {
+        let locales = locales.to_vec();
+        self.run_on_platform_thread_with_completion(move |engine| {
+            let strings = locales
+                .iter()
+                .map(LocaleStrings::new)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| EngineError::InvalidArguments)?;
+            let locales: Vec<_> = strings
+                .iter()
+                .map(LocaleStrings::as_flutter_locale)
+                .collect();
+            let pointers: Vec<_> = locales.iter().map(|locale| locale as *const _).collect();
+            unsafe {
+                EngineError::check(flutter_engine_sys::FlutterEngineUpdateLocales(
+                    engine.engine_ptr(),
+                    pointers.as_ptr() as *mut _,
+                    pointers.len(),
+                ))
+            }
+        })
+    }
+
+    /// Enable or disable semantics. While enabled the engine keeps the tree available through
+    /// `with_semantics_tree` up to date, disabling clears it.
+    pub fn update_semantics_enabled(&self, enabled: bool) -> CompletionHandle<()> {
+        self.run_on_platform_thread_with_completion(move |engine| {
+            if !enabled {
+                engine.inner.semantics_tree.write().clear();
+            }
+            unsafe {
+                EngineError::check(flutter_engine_sys::FlutterEngineUpdateSemanticsEnabled(
+                    engine.engine_ptr(),
+                    enabled,
+                ))
+            }
+        })
+    }
+
+    /// Tell the framework about the accessibility preferences of the user.
+    pub fn update_accessibility_features(
+        &self,
+        features: AccessibilityFeatures,
+    ) -> CompletionHandle<()> {
+        self.run_on_platform_thread_with_completion(move |engine| unsafe {
+            EngineError::check(
+                flutter_engine_sys::FlutterEngineUpdateAccessibilityFeatures(
+                    engine.engine_ptr(),
+                    flutter_engine_sys::FlutterAccessibilityFeature(features.bits()),
+                ),
+            )
+        })
+    }
+
+    pub fn with_semantics_tree<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&SemanticsTree) -> R,
+    {
+        f(&self.inner.semantics_tree.read())
+    }
+
+    /// Perform a single semantics action on a node, e.g. tap a button. Arguments required by
+    /// some actions are encoded with the standard codec.
+    ///
+    /// Can be called from any thread, the action is dispatched on the platform thread.
+    pub fn dispatch_semantics_action(
+        &self,
+        node_id: i32,
+        action: SemanticsActions,
+        args: Option<&Value>,
+    ) -> CompletionHandle<()> {
+        if action.bits().count_ones() != 1 {
+            return CompletionHandle::ready(Err(EngineError::InvalidArguments));
         }
+
+        let data = match args {
+            Some(args) => STANDARD_CODEC.encode_message(args),
+            None => Vec::new(),
+        };
+        self.run_on_platform_thread_with_completion(move |engine| unsafe {
+            EngineError::check(flutter_engine_sys::FlutterEngineDispatchSemanticsAction(
+                engine.engine_ptr(),
+                node_id as u64,
+                flutter_engine_sys::FlutterSemanticsAction(action.bits()),
+                data.as_ptr(),
+                data.len(),
+            ))
+        })
+    }
+
+    /// Notify the engine of a vsync by returning the baton passed to `VsyncHandler`. The frame
+    /// times are in nanoseconds of the engine clock, see `FlutterEngine::current_time`.
+    ///
+    /// Can be called from any thread, the baton is returned on the platform thread.
+    pub fn on_vsync(
+        &self,
+        baton: isize,
+        frame_start_time_nanos: u64,
+        frame_target_time_nanos: u64,
+    ) -> CompletionHandle<()> {
+        self.run_on_platform_thread_with_completion(move |engine| unsafe {
+            EngineError::check(flutter_engine_sys::FlutterEngineOnVsync(
+                engine.engine_ptr(),
+                baton,
+                frame_start_time_nanos,
+                frame_target_time_nanos,
+            ))
+        })
     }
 
-    pub(crate) fn send_platform_message(&self, message: PlatformMessage) {
+    /// Current time of the monotonic clock used by the engine, in nanoseconds.
+    pub fn current_time() -> u64 {
+        unsafe { flutter_engine_sys::FlutterEngineGetCurrentTime() }
+    }
+
+    pub(crate) fn send_platform_message(
+        &self,
+        message: PlatformMessage,
+    ) -> Result<(), EngineError> {
         trace!("Sending message on channel {}", message.channel);
         if !self.is_platform_thread() {
-            panic!("Not on platform thread");
+            return Err(EngineError::NotOnPlatformThread);
         }
 
         unsafe {
-            flutter_engine_sys::FlutterEngineSendPlatformMessage(
+            EngineError::check(flutter_engine_sys::FlutterEngineSendPlatformMessage(
                 self.engine_ptr(),
                 &message.into(),
-            );
+            ))
         }
     }
 
@@ -432,37 +901,69 @@ impl FlutterEngine {
         &self,
         response_handle: PlatformMessageResponseHandle,
         bytes: &[u8],
-    ) {
+    ) -> Result<(), EngineError> {
         trace!("Sending message response");
         if !self.is_platform_thread() {
-            panic!("Not on platform thread");
+            return Err(EngineError::NotOnPlatformThread);
         }
 
         unsafe {
-            flutter_engine_sys::FlutterEngineSendPlatformMessageResponse(
-                self.engine_ptr(),
-                response_handle.into(),
-                bytes.as_ptr(),
-                bytes.len(),
-            );
+            EngineError::check(
+                flutter_engine_sys::FlutterEngineSendPlatformMessageResponse(
+                    self.engine_ptr(),
+                    response_handle.into(),
+                    bytes.as_ptr(),
+                    bytes.len(),
+                ),
+            )
         }
     }
 
-    pub fn shutdown(&self) {
+    /// Stop the Dart isolate and release the resources of the running engine. The engine can't be
+    /// run again afterwards, but has to be shut down to be freed completely.
+    pub fn deinitialize(&self) -> Result<(), EngineError> {
         if !self.is_platform_thread() {
-            panic!("Not on platform thread")
+            return Err(EngineError::NotOnPlatformThread);
         }
 
         unsafe {
-            flutter_engine_sys::FlutterEngineShutdown(self.engine_ptr());
+            EngineError::check(flutter_engine_sys::FlutterEngineDeinitialize(
+                self.engine_ptr(),
+            ))
         }
     }
 
-    pub fn execute_platform_tasks(&self) -> Option<Instant> {
+    /// Shut down the engine and release all its resources. The engine is also shut down when the
+    /// last handle is dropped, on the platform thread the next time tasks are executed there if the
+    /// handle was dropped on another thread.
+    pub fn shutdown(&self) -> Result<(), EngineError> {
         if !self.is_platform_thread() {
-            panic!("Not on platform thread")
+            return Err(EngineError::NotOnPlatformThread);
+        }
+
+        let engine_ptr = self
+            .inner
+            .engine_ptr
+            .swap(ptr::null_mut(), Ordering::AcqRel);
+        if engine_ptr.is_null() {
+            return Ok(());
         }
+        unsafe { EngineError::check(flutter_engine_sys::FlutterEngineShutdown(engine_ptr)) }
+    }
+
+    /// Shut down the engines whose last handle was dropped on another thread and whose platform
+    /// thread is the current one. This happens whenever platform tasks are executed, so embedders
+    /// only have to call it once their loop has ended.
+    pub fn shutdown_retired_engines() {
+        shutdown_retired_engines();
+    }
 
+    pub fn execute_platform_tasks(&self) -> Result<Option<Instant>, EngineError> {
+        if !self.is_platform_thread() {
+            return Err(EngineError::NotOnPlatformThread);
+        }
+
+        shutdown_retired_engines();
         let next_task = self.inner.platform_runner.execute_tasks();
 
         let mut render_thread_fns = Vec::new();
@@ -473,39 +974,52 @@ impl FlutterEngine {
                 MainThreadCallback::RenderThread(f) => render_thread_fns.push(f),
             }
         }
+        self.inner.executor.poll_ready();
         if !render_thread_fns.is_empty() {
             let engine_copy = self.clone();
             self.post_render_thread_task(move || {
                 for f in render_thread_fns {
                     f(&engine_copy);
                 }
-            });
+            })?;
         }
 
-        next_task
+        Ok(next_task)
     }
 
-    pub(crate) fn run_task(&self, task: &FlutterTask) {
-        unsafe {
-            flutter_engine_sys::FlutterEngineRunTask(self.engine_ptr(), task as *const FlutterTask);
+    pub fn execute_render_tasks(&self) -> Result<Option<Instant>, EngineError> {
+        if !self.is_render_thread() {
+            return Err(EngineError::NotOnRenderThread);
+        }
+
+        let next_task = self
+            .inner
+            .render_runner
+            .as_ref()
+            .and_then(TaskRunner::execute_tasks);
+
+        let callbacks: Vec<MainThreadRenderThreadFn> =
+            self.inner.render_receiver.try_iter().collect();
+        for cb in callbacks {
+            cb(self);
         }
+
+        Ok(next_task)
     }
 
-    fn post_render_thread_task<F>(&self, f: F)
-    where
-        F: FnOnce() -> () + 'static,
-    {
+    pub(crate) fn run_task(&self, task: &FlutterTask) -> Result<(), EngineError> {
         unsafe {
-            let cbk = CallbackBox { cbk: Box::new(f) };
-            let b = Box::new(cbk);
-            let ptr = Box::into_raw(b);
-            flutter_engine_sys::FlutterEnginePostRenderThreadTask(
+            EngineError::check(flutter_engine_sys::FlutterEngineRunTask(
                 self.engine_ptr(),
-                Some(render_thread_task),
-                ptr as *mut c_void,
-            );
+                task as *const FlutterTask,
+            ))
         }
+    }
 
+    fn post_render_thread_task<F>(&self, f: F) -> Result<(), EngineError>
+    where
+        F: FnOnce() -> () + 'static,
+    {
         struct CallbackBox {
             pub cbk: Box<dyn FnOnce()>,
         }
@@ -515,6 +1029,22 @@ impl FlutterEngine {
             let b = Box::from_raw(ptr);
             (b.cbk)()
         }
+
+        unsafe {
+            let cbk = CallbackBox { cbk: Box::new(f) };
+            let b = Box::new(cbk);
+            let ptr = Box::into_raw(b);
+            let result = EngineError::check(flutter_engine_sys::FlutterEnginePostRenderThreadTask(
+                self.engine_ptr(),
+                Some(render_thread_task),
+                ptr as *mut c_void,
+            ));
+            if result.is_err() {
+                // the engine did not take ownership of the callback
+                drop(Box::from_raw(ptr));
+            }
+            result
+        }
     }
 
     pub fn create_texture(&self) -> Texture {
@@ -537,15 +1067,34 @@ fn path_to_cstring(path: &Path) -> CString {
 pub enum CreateError {
     NoHandler,
     EnginePtrNull,
+    Engine(EngineError),
+    /// AOT data was given to a debug engine, or is missing for a release or profile engine.
+    AotMismatch {
+        engine_runs_aot: bool,
+    },
+    AotData(String),
 }
 
 impl core::fmt::Display for CreateError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        let msg = match self {
-            CreateError::NoHandler => "No handler set.",
-            CreateError::EnginePtrNull => "Engine ptr is null.",
-        };
-        writeln!(f, "{}", msg)
+        match self {
+            CreateError::NoHandler => writeln!(f, "No handler set."),
+            CreateError::EnginePtrNull => writeln!(f, "Engine ptr is null."),
+            CreateError::Engine(error) => writeln!(f, "Failed to initialize engine: {}", error),
+            CreateError::AotMismatch {
+                engine_runs_aot: true,
+            } => writeln!(
+                f,
+                "The engine is a release or profile build and requires AOT data."
+            ),
+            CreateError::AotMismatch {
+                engine_runs_aot: false,
+            } => writeln!(
+                f,
+                "AOT data was given, but the engine is a debug build that runs kernel snapshots."
+            ),
+            CreateError::AotData(error) => writeln!(f, "Failed to load AOT data: {}", error),
+        }
     }
 }
 
@@ -9,9 +9,21 @@ use std::{
 
 use crate::FlutterEngine;
 
+struct PluginEntry {
+    plugin: Arc<RwLock<dyn Any>>,
+    init: fn(&mut dyn Any, &FlutterEngine),
+}
+
+fn init_plugin<P>(plugin: &mut dyn Any, engine: &FlutterEngine)
+where
+    P: Plugin + 'static,
+{
+    plugin.downcast_mut::<P>().unwrap().init(engine);
+}
+
 #[derive(Default)]
 pub struct PluginRegistrar {
-    plugins: HashMap<String, Arc<RwLock<dyn Any>>>,
+    plugins: HashMap<String, PluginEntry>,
 }
 
 impl PluginRegistrar {
@@ -28,17 +40,32 @@ impl PluginRegistrar {
             let mut plugin = arc.write().unwrap();
             plugin.init(engine);
         }
-        self.plugins.insert(P::plugin_name().to_owned(), arc);
+        self.plugins.insert(
+            P::plugin_name().to_owned(),
+            PluginEntry {
+                plugin: arc,
+                init: init_plugin::<P>,
+            },
+        );
         self
     }
 
+    /// Initialize all plugins again with a new engine, e.g. after the previous one was shut
+    /// down. Plugins keep their state, but register their channels with `engine`.
+    pub fn reinit(&mut self, engine: &FlutterEngine) {
+        for entry in self.plugins.values() {
+            let mut plugin = entry.plugin.write().unwrap();
+            (entry.init)(plugin.deref_mut(), engine);
+        }
+    }
+
     pub fn with_plugin<F, P>(&self, f: F)
     where
         F: FnOnce(&P),
         P: Plugin + 'static,
     {
-        if let Some(arc) = self.plugins.get(P::plugin_name()) {
-            let plugin = arc.read().unwrap();
+        if let Some(entry) = self.plugins.get(P::plugin_name()) {
+            let plugin = entry.plugin.read().unwrap();
             let plugin = plugin.deref().downcast_ref::<P>().unwrap();
             f(plugin);
         }
@@ -49,8 +76,8 @@ impl PluginRegistrar {
         F: FnOnce(&mut P),
         P: Plugin + 'static,
     {
-        if let Some(arc) = self.plugins.get_mut(P::plugin_name()) {
-            let mut plugin = arc.write().unwrap();
+        if let Some(entry) = self.plugins.get_mut(P::plugin_name()) {
+            let mut plugin = entry.plugin.write().unwrap();
             let plugin = plugin.deref_mut().downcast_mut::<P>().unwrap();
             f(plugin);
         }
@@ -0,0 +1,517 @@
+//! Rust side mirror of the semantics tree built by the framework.
+//!
+//! The tree is only populated after semantics have been enabled with
+//! `FlutterEngine::update_semantics_enabled`.
+
+use bitflags::bitflags;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+bitflags! {
+    pub struct SemanticsFlags: u32 {
+        const HAS_CHECKED_STATE = 1 << 0;
+        const IS_CHECKED = 1 << 1;
+        const IS_SELECTED = 1 << 2;
+        const IS_BUTTON = 1 << 3;
+        const IS_TEXT_FIELD = 1 << 4;
+        const IS_FOCUSED = 1 << 5;
+        const HAS_ENABLED_STATE = 1 << 6;
+        const IS_ENABLED = 1 << 7;
+        const IS_IN_MUTUALLY_EXCLUSIVE_GROUP = 1 << 8;
+        const IS_HEADER = 1 << 9;
+        const IS_OBSCURED = 1 << 10;
+        const SCOPES_ROUTE = 1 << 11;
+        const NAMES_ROUTE = 1 << 12;
+        const IS_HIDDEN = 1 << 13;
+        const IS_IMAGE = 1 << 14;
+        const IS_LIVE_REGION = 1 << 15;
+        const HAS_TOGGLED_STATE = 1 << 16;
+        const IS_TOGGLED = 1 << 17;
+        const HAS_IMPLICIT_SCROLLING = 1 << 18;
+        const IS_READ_ONLY = 1 << 20;
+        const IS_FOCUSABLE = 1 << 21;
+        const IS_LINK = 1 << 22;
+    }
+}
+
+bitflags! {
+    pub struct SemanticsActions: u32 {
+        const TAP = 1 << 0;
+        const LONG_PRESS = 1 << 1;
+        const SCROLL_LEFT = 1 << 2;
+        const SCROLL_RIGHT = 1 << 3;
+        const SCROLL_UP = 1 << 4;
+        const SCROLL_DOWN = 1 << 5;
+        const INCREASE = 1 << 6;
+        const DECREASE = 1 << 7;
+        const SHOW_ON_SCREEN = 1 << 8;
+        const MOVE_CURSOR_FORWARD_BY_CHARACTER = 1 << 9;
+        const MOVE_CURSOR_BACKWARD_BY_CHARACTER = 1 << 10;
+        const SET_SELECTION = 1 << 11;
+        const COPY = 1 << 12;
+        const CUT = 1 << 13;
+        const PASTE = 1 << 14;
+        const DID_GAIN_ACCESSIBILITY_FOCUS = 1 << 15;
+        const DID_LOSE_ACCESSIBILITY_FOCUS = 1 << 16;
+        const CUSTOM_ACTION = 1 << 17;
+        const DISMISS = 1 << 18;
+        const MOVE_CURSOR_FORWARD_BY_WORD = 1 << 19;
+        const MOVE_CURSOR_BACKWARD_BY_WORD = 1 << 20;
+    }
+}
+
+bitflags! {
+    /// Accessibility preferences of the user, reflected in the framework's `MediaQuery`.
+    pub struct AccessibilityFeatures: u32 {
+        const ACCESSIBLE_NAVIGATION = 1 << 0;
+        const INVERT_COLORS = 1 << 1;
+        const DISABLE_ANIMATIONS = 1 << 2;
+        const BOLD_TEXT = 1 << 3;
+        const REDUCE_MOTION = 1 << 4;
+    }
+}
+
+/// Coarse classification of a node, derived from its flags.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SemanticsRole {
+    Button,
+    CheckBox,
+    RadioButton,
+    Switch,
+    TextField,
+    Link,
+    Header,
+    Image,
+    Generic,
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TextDirection {
+    Unknown,
+    Rtl,
+    Ltr,
+}
+
+impl From<flutter_engine_sys::FlutterTextDirection> for TextDirection {
+    fn from(direction: flutter_engine_sys::FlutterTextDirection) -> Self {
+        match direction {
+            flutter_engine_sys::FlutterTextDirection::kFlutterTextDirectionUnknown => {
+                TextDirection::Unknown
+            }
+            flutter_engine_sys::FlutterTextDirection::kFlutterTextDirectionRTL => {
+                TextDirection::Rtl
+            }
+            flutter_engine_sys::FlutterTextDirection::kFlutterTextDirectionLTR => {
+                TextDirection::Ltr
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub left: f64,
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SemanticsNode {
+    pub id: i32,
+    pub flags: SemanticsFlags,
+    pub actions: SemanticsActions,
+    pub text_selection_base: i32,
+    pub text_selection_extent: i32,
+    pub scroll_child_count: i32,
+    pub scroll_index: i32,
+    pub scroll_position: f64,
+    pub scroll_extent_max: f64,
+    pub scroll_extent_min: f64,
+    pub elevation: f64,
+    pub thickness: f64,
+    pub label: String,
+    pub hint: String,
+    pub value: String,
+    pub increased_value: String,
+    pub decreased_value: String,
+    pub text_direction: TextDirection,
+    /// Bounding box in the coordinate system of this node.
+    pub rect: Rect,
+    /// Row-major 3x3 transform from this node's coordinate system to its parent's.
+    pub transform: [f64; 9],
+    pub children_in_traversal_order: Vec<i32>,
+    pub children_in_hit_test_order: Vec<i32>,
+    pub custom_actions: Vec<i32>,
+    pub platform_view_id: i64,
+}
+
+impl SemanticsNode {
+    pub fn role(&self) -> SemanticsRole {
+        let flags = self.flags;
+        if flags.contains(SemanticsFlags::IS_BUTTON) {
+            SemanticsRole::Button
+        } else if flags.contains(SemanticsFlags::IS_TEXT_FIELD) {
+            SemanticsRole::TextField
+        } else if flags.contains(SemanticsFlags::IS_LINK) {
+            SemanticsRole::Link
+        } else if flags.contains(SemanticsFlags::HAS_CHECKED_STATE) {
+            if flags.contains(SemanticsFlags::IS_IN_MUTUALLY_EXCLUSIVE_GROUP) {
+                SemanticsRole::RadioButton
+            } else {
+                SemanticsRole::CheckBox
+            }
+        } else if flags.contains(SemanticsFlags::HAS_TOGGLED_STATE) {
+            SemanticsRole::Switch
+        } else if flags.contains(SemanticsFlags::IS_HEADER) {
+            SemanticsRole::Header
+        } else if flags.contains(SemanticsFlags::IS_IMAGE) {
+            SemanticsRole::Image
+        } else {
+            SemanticsRole::Generic
+        }
+    }
+}
+
+impl From<&flutter_engine_sys::FlutterSemanticsNode> for SemanticsNode {
+    fn from(node: &flutter_engine_sys::FlutterSemanticsNode) -> Self {
+        let transform = node.transform;
+        unsafe {
+            Self {
+                id: node.id,
+                flags: SemanticsFlags::from_bits_truncate(node.flags.0),
+                actions: SemanticsActions::from_bits_truncate(node.actions.0),
+                text_selection_base: node.text_selection_base,
+                text_selection_extent: node.text_selection_extent,
+                scroll_child_count: node.scroll_child_count,
+                scroll_index: node.scroll_index,
+                scroll_position: node.scroll_position,
+                scroll_extent_max: node.scroll_extent_max,
+                scroll_extent_min: node.scroll_extent_min,
+                elevation: node.elevation,
+                thickness: node.thickness,
+                label: string_from_ptr(node.label),
+                hint: string_from_ptr(node.hint),
+                value: string_from_ptr(node.value),
+                increased_value: string_from_ptr(node.increased_value),
+                decreased_value: string_from_ptr(node.decreased_value),
+                text_direction: node.text_direction.into(),
+                rect: Rect {
+                    left: node.rect.left,
+                    top: node.rect.top,
+                    right: node.rect.right,
+                    bottom: node.rect.bottom,
+                },
+                transform: [
+                    transform.scaleX,
+                    transform.skewX,
+                    transform.transX,
+                    transform.skewY,
+                    transform.scaleY,
+                    transform.transY,
+                    transform.pers0,
+                    transform.pers1,
+                    transform.pers2,
+                ],
+                children_in_traversal_order: vec_from_ptr(
+                    node.children_in_traversal_order,
+                    node.child_count,
+                ),
+                children_in_hit_test_order: vec_from_ptr(
+                    node.children_in_hit_test_order,
+                    node.child_count,
+                ),
+                custom_actions: vec_from_ptr(
+                    node.custom_accessibility_actions,
+                    node.custom_accessibility_actions_count,
+                ),
+                platform_view_id: node.platform_view_id,
+            }
+        }
+    }
+}
+
+/// A custom action, or an override of the label of a standard action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomSemanticsAction {
+    pub id: i32,
+    /// The standard action this overrides, empty for custom actions.
+    pub override_action: SemanticsActions,
+    pub label: String,
+    pub hint: String,
+}
+
+impl From<&flutter_engine_sys::FlutterSemanticsCustomAction> for CustomSemanticsAction {
+    fn from(action: &flutter_engine_sys::FlutterSemanticsCustomAction) -> Self {
+        unsafe {
+            Self {
+                id: action.id,
+                override_action: SemanticsActions::from_bits_truncate(action.override_action.0),
+                label: string_from_ptr(action.label),
+                hint: string_from_ptr(action.hint),
+            }
+        }
+    }
+}
+
+unsafe fn string_from_ptr(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn vec_from_ptr(ptr: *const i32, len: usize) -> Vec<i32> {
+    if ptr.is_null() {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(ptr, len).to_vec()
+    }
+}
+
+/// The engine sends updated nodes one at a time, followed by a batch end marker. Updates are
+/// buffered until the batch ends, so the tree never exposes a half applied update.
+#[derive(Clone, Default)]
+pub struct SemanticsTree {
+    nodes: HashMap<i32, SemanticsNode>,
+    custom_actions: HashMap<i32, CustomSemanticsAction>,
+    pending_nodes: Vec<SemanticsNode>,
+    pending_custom_actions: Vec<CustomSemanticsAction>,
+    generation: u64,
+}
+
+impl SemanticsTree {
+    pub const ROOT_ID: i32 = 0;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub(crate) fn push_node(&mut self, node: SemanticsNode) {
+        self.pending_nodes.push(node);
+    }
+
+    pub(crate) fn push_custom_action(&mut self, action: CustomSemanticsAction) {
+        self.pending_custom_actions.push(action);
+    }
+
+    /// Apply all buffered node updates and drop nodes that are no longer part of the tree.
+    pub(crate) fn commit_nodes(&mut self) {
+        for node in self.pending_nodes.drain(..) {
+            self.nodes.insert(node.id, node);
+        }
+
+        let reachable: HashSet<i32> = self.iter().map(|node| node.id).collect();
+        self.nodes.retain(|id, _| reachable.contains(id));
+        self.generation += 1;
+    }
+
+    pub(crate) fn commit_custom_actions(&mut self) {
+        for action in self.pending_custom_actions.drain(..) {
+            self.custom_actions.insert(action.id, action);
+        }
+        self.generation += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.custom_actions.clear();
+        self.pending_nodes.clear();
+        self.pending_custom_actions.clear();
+        self.generation += 1;
+    }
+
+    /// Apply a batch of node updates at once, like the engine does at the end of each batch.
+    pub fn update<I>(&mut self, nodes: I)
+    where
+        I: IntoIterator<Item = SemanticsNode>,
+    {
+        self.pending_nodes.extend(nodes);
+        self.commit_nodes();
+    }
+
+    /// Incremented whenever the tree changes, so consumers can cheaply detect updates.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn root(&self) -> Option<&SemanticsNode> {
+        self.nodes.get(&Self::ROOT_ID)
+    }
+
+    pub fn node(&self, id: i32) -> Option<&SemanticsNode> {
+        self.nodes.get(&id)
+    }
+
+    pub fn custom_action(&self, id: i32) -> Option<&CustomSemanticsAction> {
+        self.custom_actions.get(&id)
+    }
+
+    pub fn children<'a>(
+        &'a self,
+        node: &'a SemanticsNode,
+    ) -> impl Iterator<Item = &'a SemanticsNode> + 'a {
+        node.children_in_traversal_order
+            .iter()
+            .filter_map(move |id| self.nodes.get(id))
+    }
+
+    pub fn parent(&self, node: &SemanticsNode) -> Option<&SemanticsNode> {
+        self.nodes
+            .values()
+            .find(|parent| parent.children_in_traversal_order.contains(&node.id))
+    }
+
+    /// Iterate over all nodes depth first in traversal order, starting at the root.
+    pub fn iter(&self) -> impl Iterator<Item = &SemanticsNode> {
+        let mut stack: Vec<&SemanticsNode> = self.root().into_iter().collect();
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(
+                node.children_in_traversal_order
+                    .iter()
+                    .rev()
+                    .filter_map(|id| self.nodes.get(id)),
+            );
+            Some(node)
+        })
+    }
+
+    pub fn find_by_label<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a SemanticsNode> {
+        self.iter().filter(move |node| node.label == label)
+    }
+
+    pub fn find_by_role(&self, role: SemanticsRole) -> impl Iterator<Item = &SemanticsNode> {
+        self.iter().filter(move |node| node.role() == role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: i32, children: &[i32], label: &str, flags: SemanticsFlags) -> SemanticsNode {
+        SemanticsNode {
+            id,
+            flags,
+            actions: SemanticsActions::empty(),
+            text_selection_base: -1,
+            text_selection_extent: -1,
+            scroll_child_count: 0,
+            scroll_index: 0,
+            scroll_position: 0.0,
+            scroll_extent_max: 0.0,
+            scroll_extent_min: 0.0,
+            elevation: 0.0,
+            thickness: 0.0,
+            label: label.to_owned(),
+            hint: String::new(),
+            value: String::new(),
+            increased_value: String::new(),
+            decreased_value: String::new(),
+            text_direction: TextDirection::Ltr,
+            rect: Rect {
+                left: 0.0,
+                top: 0.0,
+                right: 100.0,
+                bottom: 100.0,
+            },
+            transform: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            children_in_traversal_order: children.to_vec(),
+            children_in_hit_test_order: children.to_vec(),
+            custom_actions: Vec::new(),
+            platform_view_id: -1,
+        }
+    }
+
+    fn ids(tree: &SemanticsTree) -> Vec<i32> {
+        tree.iter().map(|node| node.id).collect()
+    }
+
+    fn sample_tree() -> SemanticsTree {
+        let mut tree = SemanticsTree::new();
+        tree.update(vec![
+            node(0, &[1, 2], "", SemanticsFlags::empty()),
+            node(1, &[3], "Settings", SemanticsFlags::IS_HEADER),
+            node(2, &[], "OK", SemanticsFlags::IS_BUTTON),
+            node(3, &[], "Cancel", SemanticsFlags::IS_BUTTON),
+        ]);
+        tree
+    }
+
+    #[test]
+    fn test_pending_batch() {
+        let mut tree = SemanticsTree::new();
+        tree.push_node(node(0, &[1], "", SemanticsFlags::empty()));
+        tree.push_node(node(1, &[], "OK", SemanticsFlags::IS_BUTTON));
+        assert!(tree.is_empty());
+        assert_eq!(tree.generation(), 0);
+
+        tree.commit_nodes();
+        assert_eq!(ids(&tree), vec![0, 1]);
+
+        tree.push_node(node(1, &[], "Cancel", SemanticsFlags::IS_BUTTON));
+        assert_eq!(tree.node(1).unwrap().label, "OK");
+        tree.commit_nodes();
+        assert_eq!(tree.node(1).unwrap().label, "Cancel");
+
+        tree.push_custom_action(CustomSemanticsAction {
+            id: 7,
+            override_action: SemanticsActions::TAP,
+            label: "Open".to_owned(),
+            hint: String::new(),
+        });
+        assert!(tree.custom_action(7).is_none());
+        tree.commit_custom_actions();
+        assert_eq!(tree.custom_action(7).unwrap().label, "Open");
+    }
+
+    #[test]
+    fn test_prune_unreachable() {
+        let mut tree = sample_tree();
+        assert_eq!(ids(&tree), vec![0, 1, 3, 2]);
+
+        tree.update(vec![node(0, &[2], "", SemanticsFlags::empty())]);
+        assert_eq!(ids(&tree), vec![0, 2]);
+        assert!(tree.node(1).is_none());
+        assert!(tree.node(3).is_none());
+
+        // nodes only become part of the tree once a parent refers to them
+        tree.update(vec![node(4, &[], "Orphan", SemanticsFlags::empty())]);
+        assert!(tree.node(4).is_none());
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut tree = SemanticsTree::new();
+        assert_eq!(tree.generation(), 0);
+        tree.update(vec![node(0, &[], "", SemanticsFlags::empty())]);
+        assert_eq!(tree.generation(), 1);
+        tree.commit_custom_actions();
+        assert_eq!(tree.generation(), 2);
+        tree.clear();
+        assert_eq!(tree.generation(), 3);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_find() {
+        let tree = sample_tree();
+        let labels: Vec<&str> = tree
+            .find_by_role(SemanticsRole::Button)
+            .map(|node| node.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["Cancel", "OK"]);
+
+        let header = tree.find_by_label("Settings").next().unwrap();
+        assert_eq!(header.role(), SemanticsRole::Header);
+        assert_eq!(tree.parent(header).unwrap().id, SemanticsTree::ROOT_ID);
+        assert_eq!(tree.find_by_label("Missing").count(), 0);
+    }
+}
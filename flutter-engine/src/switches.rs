@@ -0,0 +1,225 @@
+//! Command line switches of the engine.
+
+use log::warn;
+use std::env;
+use std::path::PathBuf;
+
+/// Switches passed to the engine on startup. Switches without a dedicated field can be added to
+/// `extra`, including the leading `--`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EngineSwitches {
+    /// Port of the observatory, 0 picks a free port.
+    pub observatory_port: Option<u16>,
+    pub disable_service_auth_codes: bool,
+    pub trace_skia: bool,
+    pub verbose_logging: bool,
+    pub enable_dart_profiling: bool,
+    /// Directory the engine stores its caches in.
+    pub cache_dir_path: Option<PathBuf>,
+    /// Store shaders in the persistent cache as SkSL, which can be exported and shipped to warm
+    /// up the cache on other machines.
+    pub cache_sksl: bool,
+    pub extra: Vec<String>,
+}
+
+impl EngineSwitches {
+    /// Parse switches of the form `--name` or `--name=value`. Unknown switches are kept in
+    /// `extra`.
+    pub fn parse<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut switches = Self::default();
+        for arg in args {
+            switches.add(arg.as_ref());
+        }
+        switches
+    }
+
+    /// Read switches from the environment, using the same variables as the embedders of the
+    /// Flutter project: `FLUTTER_ENGINE_SWITCHES` holds the number of switches, which are read
+    /// from `FLUTTER_ENGINE_SWITCH_1` and following, without the leading `--`.
+    pub fn from_env() -> Self {
+        let count = match env::var("FLUTTER_ENGINE_SWITCHES") {
+            Ok(count) => count,
+            Err(_) => return Self::default(),
+        };
+        let count: usize = match count.parse() {
+            Ok(count) => count,
+            Err(_) => {
+                warn!("Invalid value of FLUTTER_ENGINE_SWITCHES: {}", count);
+                return Self::default();
+            }
+        };
+        Self::parse(
+            (1..=count)
+                .filter_map(|index| env::var(format!("FLUTTER_ENGINE_SWITCH_{}", index)).ok())
+                .map(|switch| format!("--{}", switch)),
+        )
+    }
+
+    fn add(&mut self, arg: &str) {
+        let (name, value) = match arg.find('=') {
+            Some(index) => (&arg[..index], Some(&arg[index + 1..])),
+            None => (arg, None),
+        };
+        match (name, value) {
+            ("--observatory-port", Some(port)) => match port.parse() {
+                Ok(port) => self.observatory_port = Some(port),
+                Err(_) => warn!("Invalid observatory port: {}", port),
+            },
+            ("--disable-service-auth-codes", None) => self.disable_service_auth_codes = true,
+            ("--trace-skia", None) => self.trace_skia = true,
+            ("--verbose-logging", None) => self.verbose_logging = true,
+            ("--enable-dart-profiling", None) => self.enable_dart_profiling = true,
+            ("--cache-dir-path", Some(path)) => self.cache_dir_path = Some(path.into()),
+            ("--cache-sksl", None) => self.cache_sksl = true,
+            _ => self.extra.push(arg.to_owned()),
+        }
+    }
+
+    /// Apply all switches set in `other` on top of these.
+    pub fn merge(&mut self, other: EngineSwitches) {
+        if other.observatory_port.is_some() {
+            self.observatory_port = other.observatory_port;
+        }
+        self.disable_service_auth_codes |= other.disable_service_auth_codes;
+        self.trace_skia |= other.trace_skia;
+        self.verbose_logging |= other.verbose_logging;
+        self.enable_dart_profiling |= other.enable_dart_profiling;
+        if other.cache_dir_path.is_some() {
+            self.cache_dir_path = other.cache_dir_path;
+        }
+        self.cache_sksl |= other.cache_sksl;
+        self.extra.extend(other.extra);
+    }
+
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(port) = self.observatory_port {
+            args.push(format!("--observatory-port={}", port));
+        }
+        if self.disable_service_auth_codes {
+            args.push("--disable-service-auth-codes".to_owned());
+        }
+        if self.trace_skia {
+            args.push("--trace-skia".to_owned());
+        }
+        if self.verbose_logging {
+            args.push("--verbose-logging".to_owned());
+        }
+        if self.enable_dart_profiling {
+            args.push("--enable-dart-profiling".to_owned());
+        }
+        if let Some(path) = &self.cache_dir_path {
+            args.push(format!("--cache-dir-path={}", path.display()));
+        }
+        if self.cache_sksl {
+            args.push("--cache-sksl".to_owned());
+        }
+        args.extend(self.extra.iter().cloned());
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let switches = EngineSwitches::parse(&[
+            "--observatory-port=8080",
+            "--trace-skia",
+            "--cache-dir-path=/tmp/flutter",
+            "--enable-software-rendering",
+            "--observatory-port=port",
+            "--trace-skia=true",
+        ]);
+        assert_eq!(
+            switches,
+            EngineSwitches {
+                observatory_port: Some(8080),
+                trace_skia: true,
+                cache_dir_path: Some("/tmp/flutter".into()),
+                extra: vec![
+                    "--enable-software-rendering".to_owned(),
+                    "--trace-skia=true".to_owned()
+                ],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        env::set_var("FLUTTER_ENGINE_SWITCHES", "3");
+        env::set_var("FLUTTER_ENGINE_SWITCH_1", "verbose-logging");
+        env::set_var("FLUTTER_ENGINE_SWITCH_2", "observatory-port=0");
+        // missing switches are skipped
+        env::remove_var("FLUTTER_ENGINE_SWITCH_3");
+        env::set_var("FLUTTER_ENGINE_SWITCH_4", "trace-skia");
+        assert_eq!(
+            EngineSwitches::from_env(),
+            EngineSwitches {
+                observatory_port: Some(0),
+                verbose_logging: true,
+                ..Default::default()
+            }
+        );
+
+        env::set_var("FLUTTER_ENGINE_SWITCHES", "all");
+        assert_eq!(EngineSwitches::from_env(), EngineSwitches::default());
+
+        env::remove_var("FLUTTER_ENGINE_SWITCHES");
+        assert_eq!(EngineSwitches::from_env(), EngineSwitches::default());
+        env::remove_var("FLUTTER_ENGINE_SWITCH_1");
+        env::remove_var("FLUTTER_ENGINE_SWITCH_2");
+        env::remove_var("FLUTTER_ENGINE_SWITCH_4");
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut switches = EngineSwitches::parse(&[
+            "--observatory-port=8080",
+            "--cache-dir-path=/tmp/flutter",
+            "--verbose-logging",
+            "--foo",
+        ]);
+        switches.merge(EngineSwitches::parse(&[
+            "--observatory-port=0",
+            "--cache-sksl",
+            "--bar",
+        ]));
+        assert_eq!(
+            switches,
+            EngineSwitches {
+                observatory_port: Some(0),
+                verbose_logging: true,
+                cache_dir_path: Some("/tmp/flutter".into()),
+                cache_sksl: true,
+                extra: vec!["--foo".to_owned(), "--bar".to_owned()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_args() {
+        let args = [
+            "--observatory-port=0",
+            "--disable-service-auth-codes",
+            "--trace-skia",
+            "--verbose-logging",
+            "--enable-dart-profiling",
+            "--cache-dir-path=/tmp/flutter",
+            "--cache-sksl",
+            "--foo=bar",
+        ];
+        let switches = EngineSwitches::parse(&args);
+        assert_eq!(switches.to_args(), args);
+        assert_eq!(EngineSwitches::parse(switches.to_args()), switches);
+        assert!(EngineSwitches::default().to_args().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::FlutterEngineWeakRef;
 use flutter_engine_sys::{FlutterEngineGetCurrentTime, FlutterTask};
-use log::debug;
+use log::{debug, error};
 use parking_lot::{Mutex, MutexGuard};
 use priority_queue::PriorityQueue;
 use std::cmp::Ordering;
@@ -15,9 +15,74 @@ pub trait TaskRunnerHandler {
     fn wake(&self);
 }
 
+/// The time source of a task runner, used to convert the target times of engine tasks.
+pub trait Clock {
+    /// The current time on the engine's clock in nanoseconds, see
+    /// `FlutterEngine::current_time`.
+    fn engine_time(&self) -> u64;
+
+    /// The current time, at the same moment as `engine_time`.
+    fn now(&self) -> Instant;
+}
+
+/// The clocks of the engine and the OS.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn engine_time(&self) -> u64 {
+        unsafe { FlutterEngineGetCurrentTime() }
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when it is advanced, so tasks can be scheduled deterministically in
+/// tests. The engine time starts at 0.
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::from_secs(0)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock() += duration;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn engine_time(&self) -> u64 {
+        self.elapsed().as_nanos() as u64
+    }
+
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
 pub(crate) struct TaskRunnerInner {
     engine: FlutterEngineWeakRef,
     pub(crate) handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+    clock: Arc<dyn Clock + Send + Sync>,
     thread_id: ThreadId,
     tasks: PriorityQueue<Task, TaskPriority>,
 }
@@ -42,12 +107,31 @@ impl TaskRunnerInner {
 
 impl TaskRunner {
     pub fn new(handler: Arc<dyn TaskRunnerHandler + Send + Sync>) -> Self {
-        let thread_id = thread::current().id();
-        debug!("task runner created on thread {:?}", thread_id);
+        Self::new_for_thread(handler, thread::current().id())
+    }
+
+    /// Create a task runner whose tasks will be executed on the given thread. This allows setting
+    /// up a runner for a thread other than the current one, e.g. a dedicated render thread.
+    pub fn new_for_thread(
+        handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+        thread_id: ThreadId,
+    ) -> Self {
+        Self::with_clock(handler, thread_id, Arc::new(SystemClock))
+    }
+
+    /// Create a task runner which schedules tasks according to `clock` instead of the system
+    /// clock.
+    pub fn with_clock(
+        handler: Arc<dyn TaskRunnerHandler + Send + Sync>,
+        thread_id: ThreadId,
+        clock: Arc<dyn Clock + Send + Sync>,
+    ) -> Self {
+        debug!("task runner created for thread {:?}", thread_id);
         Self {
             inner: Arc::new(Mutex::new(TaskRunnerInner {
                 engine: Default::default(),
                 handler,
+                clock,
                 thread_id,
                 tasks: PriorityQueue::new(),
             })),
@@ -59,42 +143,55 @@ impl TaskRunner {
         inner.engine = engine;
     }
 
+    pub(crate) fn engine(&self) -> FlutterEngineWeakRef {
+        self.inner.lock().engine.clone()
+    }
+
+    /// Run all tasks which are due and return when the next task is due.
     pub fn execute_tasks(&self) -> Option<Instant> {
-        let now = Instant::now();
-        let mut expired_tasks = Vec::new();
+        // make sure to unlock mutex before actually running the tasks as they may post another task
+        let expired_tasks = self.take_expired_tasks();
 
-        let engine = {
-            let mut inner = self.inner.lock();
-            let tasks = &mut inner.tasks;
-            while let Some((_, priority)) = tasks.peek() {
-                if priority.time > now {
-                    break;
+        if !expired_tasks.is_empty() {
+            match self.engine().upgrade() {
+                Some(engine) => {
+                    for task in expired_tasks {
+                        if let Err(err) = engine.run_task(&task) {
+                            error!("Failed to run task: {}", err);
+                        }
+                    }
                 }
-                let (task, _) = tasks.pop().unwrap();
-                expired_tasks.push(task);
+                None => error!("Engine dropped before its tasks were run"),
             }
-            // make sure to unlock mutex before actually running the tasks as they may post another task
-            inner.engine.upgrade().unwrap()
-        };
-
-        // run tasks
-        for task in expired_tasks {
-            engine.run_task(&task.task);
         }
 
-        // next task time
+        self.next_task_time()
+    }
+
+    /// Remove all tasks which are due from the queue, in the order they have to run in.
+    fn take_expired_tasks(&self) -> Vec<FlutterTask> {
         let mut inner = self.inner.lock();
+        let now = inner.clock.now();
         let tasks = &mut inner.tasks;
-        if let Some((_, priority)) = tasks.peek() {
-            Some(priority.time)
-        } else {
-            None
+        let mut expired_tasks = Vec::new();
+        while let Some((_, priority)) = tasks.peek() {
+            if priority.time > now {
+                break;
+            }
+            let (task, _) = tasks.pop().unwrap();
+            expired_tasks.push(task.task);
         }
+        expired_tasks
+    }
+
+    fn next_task_time(&self) -> Option<Instant> {
+        let inner = self.inner.lock();
+        inner.tasks.peek().map(|(_, priority)| priority.time)
     }
 
-    fn flutter_time_to_instant(target_time_nanos: u64) -> Instant {
-        let current_time = unsafe { FlutterEngineGetCurrentTime() };
-        let now = Instant::now();
+    fn flutter_time_to_instant(clock: &dyn Clock, target_time_nanos: u64) -> Instant {
+        let current_time = clock.engine_time();
+        let now = clock.now();
         if current_time >= target_time_nanos {
             return now;
         }
@@ -110,7 +207,7 @@ impl TaskRunner {
     ) {
         static GLOBAL_ORDER: AtomicU64 = AtomicU64::new(0);
         let task_priority = TaskPriority {
-            time: Self::flutter_time_to_instant(target_time_nanos),
+            time: Self::flutter_time_to_instant(&*guard.clock, target_time_nanos),
             order: GLOBAL_ORDER.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
         };
         let task = Task { task };
@@ -129,9 +226,12 @@ impl TaskRunner {
         self.inner.lock().runs_task_on_current_thread()
     }
 
+    pub(crate) fn handler(&self) -> Arc<dyn TaskRunnerHandler + Send + Sync> {
+        self.inner.lock().handler.clone()
+    }
+
     pub(crate) fn wake(&self) {
-        let handler = { self.inner.lock().handler.clone() };
-        handler.wake();
+        self.handler().wake();
     }
 }
 
@@ -145,10 +245,12 @@ struct Task {
     task: FlutterTask,
 }
 
+// The queue returns the task with the greatest priority first, which has to be the one that is
+// due first. Tasks due at the same time run in the order they were posted in.
 impl Ord for TaskPriority {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.time.cmp(&other.time) {
-            Ordering::Equal => self.order.cmp(&other.order),
+        match other.time.cmp(&self.time) {
+            Ordering::Equal => other.order.cmp(&self.order),
             ord => ord,
         }
     }
@@ -174,3 +276,130 @@ impl Hash for Task {
         std::ptr::hash(self.task.runner, state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct CountingHandler {
+        wakes: AtomicUsize,
+    }
+
+    impl TaskRunnerHandler for CountingHandler {
+        fn wake(&self) {
+            self.wakes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    fn runner() -> (TaskRunner, Arc<ManualClock>, Arc<CountingHandler>) {
+        let clock = Arc::new(ManualClock::new());
+        let handler = Arc::new(CountingHandler::default());
+        let runner = TaskRunner::with_clock(handler.clone(), thread::current().id(), clock.clone());
+        (runner, clock, handler)
+    }
+
+    fn post(runner: &TaskRunner, task: u64, target_time: Duration) {
+        let task = FlutterTask {
+            runner: ptr::null_mut(),
+            task,
+        };
+        TaskRunner::post_task(
+            &mut runner.inner.lock(),
+            task,
+            target_time.as_nanos() as u64,
+        );
+    }
+
+    fn expired(runner: &TaskRunner) -> Vec<u64> {
+        runner
+            .take_expired_tasks()
+            .iter()
+            .map(|task| task.task)
+            .collect()
+    }
+
+    #[test]
+    fn test_tasks_run_in_order_of_target_time() {
+        let (runner, clock, _) = runner();
+        post(&runner, 1, Duration::from_millis(30));
+        post(&runner, 2, Duration::from_millis(10));
+        post(&runner, 3, Duration::from_millis(20));
+
+        assert!(expired(&runner).is_empty());
+        clock.advance(Duration::from_millis(25));
+        assert_eq!(expired(&runner), vec![2, 3]);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(expired(&runner), vec![1]);
+        assert!(expired(&runner).is_empty());
+    }
+
+    #[test]
+    fn test_tasks_due_at_the_same_time_run_in_posting_order() {
+        let (runner, clock, _) = runner();
+        post(&runner, 5, Duration::from_millis(10));
+        post(&runner, 3, Duration::from_millis(10));
+        post(&runner, 4, Duration::from_millis(10));
+        post(&runner, 1, Duration::from_millis(5));
+
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(expired(&runner), vec![1, 5, 3, 4]);
+    }
+
+    #[test]
+    fn test_task_is_due_when_the_clock_reaches_it() {
+        let (runner, clock, _) = runner();
+        let start = clock.now();
+        post(&runner, 1, Duration::from_millis(10));
+
+        assert_eq!(
+            runner.execute_tasks(),
+            Some(start + Duration::from_millis(10))
+        );
+        assert!(expired(&runner).is_empty());
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(expired(&runner), vec![1]);
+        assert_eq!(runner.execute_tasks(), None);
+    }
+
+    #[test]
+    fn test_tasks_in_the_past_are_due_immediately() {
+        let (runner, clock, _) = runner();
+        clock.advance(Duration::from_millis(100));
+        post(&runner, 1, Duration::from_millis(50));
+        assert_eq!(expired(&runner), vec![1]);
+    }
+
+    #[test]
+    fn test_execute_tasks_returns_next_wakeup() {
+        let (runner, clock, _) = runner();
+        let start = clock.now();
+        assert_eq!(runner.execute_tasks(), None);
+
+        post(&runner, 1, Duration::from_millis(50));
+        post(&runner, 2, Duration::from_millis(20));
+        assert_eq!(
+            runner.execute_tasks(),
+            Some(start + Duration::from_millis(20))
+        );
+
+        // there is no engine to run the expired task, but it is still removed from the queue
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(
+            runner.execute_tasks(),
+            Some(start + Duration::from_millis(50))
+        );
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(runner.execute_tasks(), None);
+    }
+
+    #[test]
+    fn test_post_task_wakes_handler() {
+        let (runner, _, handler) = runner();
+        post(&runner, 1, Duration::from_millis(10));
+        post(&runner, 2, Duration::from_millis(20));
+        assert_eq!(handler.wakes.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::EngineError;
 use crate::FlutterEngine;
 use flutter_engine_sys::FlutterOpenGLTexture;
 #[cfg(feature = "image")]
@@ -7,6 +8,7 @@ use std::collections::HashMap;
 use std::os::raw::c_void;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
+use tracing::trace_span;
 
 pub(crate) struct TextureRegistry {
     last_id: AtomicI64,
@@ -26,11 +28,14 @@ impl TextureRegistry {
 
         engine.run_on_platform_thread(move |engine| {
             log::trace!("texture {}: register", texture_id);
-            unsafe {
-                flutter_engine_sys::FlutterEngineRegisterExternalTexture(
+            let result = unsafe {
+                EngineError::check(flutter_engine_sys::FlutterEngineRegisterExternalTexture(
                     engine.engine_ptr(),
                     texture_id,
-                );
+                ))
+            };
+            if let Err(err) = result {
+                log::error!("Failed to register texture {}: {}", texture_id, err);
             }
         });
 
@@ -117,6 +122,8 @@ fn post_frame_internal(
     frames: &Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
     frame: TextureFrame,
 ) {
+    let span = trace_span!("texture_post_frame", texture_id);
+    let _enter = span.enter();
     if let Some(old_frame) = frames.lock().insert(texture_id, frame) {
         engine.run_on_render_thread(move |_| {
             (old_frame.destruction_callback)();
@@ -125,11 +132,22 @@ fn post_frame_internal(
 
     let texture_id = texture_id;
     engine.run_on_platform_thread(move |engine| {
+        let span = trace_span!("texture_frame_available", texture_id);
+        let _enter = span.enter();
         log::trace!("texture {}: marking frame available", texture_id);
-        unsafe {
-            flutter_engine_sys::FlutterEngineMarkExternalTextureFrameAvailable(
-                engine.engine_ptr(),
+        let result = unsafe {
+            EngineError::check(
+                flutter_engine_sys::FlutterEngineMarkExternalTextureFrameAvailable(
+                    engine.engine_ptr(),
+                    texture_id,
+                ),
+            )
+        };
+        if let Err(err) = result {
+            log::error!(
+                "Failed to mark frame available for texture {}: {}",
                 texture_id,
+                err
             );
         }
     });
@@ -140,11 +158,14 @@ impl Drop for Texture {
         let texture_id = self.texture_id;
         self.engine.run_on_platform_thread(move |engine| {
             log::trace!("texture {}: unregister", texture_id);
-            unsafe {
-                flutter_engine_sys::FlutterEngineUnregisterExternalTexture(
+            let result = unsafe {
+                EngineError::check(flutter_engine_sys::FlutterEngineUnregisterExternalTexture(
                     engine.engine_ptr(),
                     texture_id,
-                );
+                ))
+            };
+            if let Err(err) = result {
+                log::error!("Failed to unregister texture {}: {}", texture_id, err);
             }
         });
     }
@@ -0,0 +1,222 @@
+//! Events on the engine's timeline, shown in the Dart DevTools next to the framework's events.
+//!
+//! The engine only records events while the timeline is enabled, e.g. with the
+//! `--trace-startup` switch or from DevTools. Otherwise they are discarded.
+
+use parking_lot::{const_rwlock, RwLock};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+
+/// The engine keeps the names of events without copying them, so every distinct name is turned
+/// into a C string once and leaked. Names are `'static`, so their number is bounded by the
+/// callsites using them.
+fn intern(name: &'static str) -> &'static CStr {
+    static NAMES: RwLock<Option<HashMap<&'static str, &'static CStr>>> = const_rwlock(None);
+    if let Some(name) = NAMES.read().as_ref().and_then(|names| names.get(name)) {
+        return name;
+    }
+    NAMES
+        .write()
+        .get_or_insert_with(HashMap::new)
+        .entry(name)
+        .or_insert_with(|| {
+            let name = CString::new(name.replace('\0', "")).unwrap();
+            Box::leak(name.into_boxed_c_str())
+        })
+}
+
+/// Begin a duration event. Must be balanced with a call to `duration_end` with the same name on
+/// the same thread.
+pub fn duration_begin(name: &'static str) {
+    sys::duration_begin(intern(name))
+}
+
+pub fn duration_end(name: &'static str) {
+    sys::duration_end(intern(name))
+}
+
+pub fn instant(name: &'static str) {
+    sys::instant(intern(name))
+}
+
+#[cfg(not(test))]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) fn duration_begin(name: &'static CStr) {
+        unsafe { flutter_engine_sys::FlutterEngineTraceEventDurationBegin(name.as_ptr()) }
+    }
+
+    pub(super) fn duration_end(name: &'static CStr) {
+        unsafe { flutter_engine_sys::FlutterEngineTraceEventDurationEnd(name.as_ptr()) }
+    }
+
+    pub(super) fn instant(name: &'static CStr) {
+        unsafe { flutter_engine_sys::FlutterEngineTraceEventInstant(name.as_ptr()) }
+    }
+}
+
+/// Records the events instead of passing them to the engine.
+#[cfg(test)]
+mod sys {
+    use parking_lot::{const_mutex, Mutex};
+    use std::ffi::CStr;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub(super) enum Record {
+        Begin(&'static CStr),
+        End(&'static CStr),
+        Instant(&'static CStr),
+    }
+
+    pub(super) static RECORDS: Mutex<Vec<Record>> = const_mutex(Vec::new());
+
+    pub(super) fn duration_begin(name: &'static CStr) {
+        RECORDS.lock().push(Record::Begin(name));
+    }
+
+    pub(super) fn duration_end(name: &'static CStr) {
+        RECORDS.lock().push(Record::End(name));
+    }
+
+    pub(super) fn instant(name: &'static CStr) {
+        RECORDS.lock().push(Record::Instant(name));
+    }
+}
+
+#[cfg(feature = "tracing-layer")]
+pub use self::layer::TimelineLayer;
+
+#[cfg(feature = "tracing-layer")]
+mod layer {
+    use std::ffi::CStr;
+    use tracing::span::{Attributes, Id};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// The interned name of a span, looked up once when the span is created.
+    struct TimelineName(&'static CStr);
+
+    /// Forwards `tracing` spans as duration events and events as instant events to the engine's
+    /// timeline. Spans and events are named after their `tracing` names.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TimelineLayer;
+
+    impl<S> Layer<S> for TimelineLayer
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                let name = super::intern(attrs.metadata().name());
+                span.extensions_mut().insert(TimelineName(name));
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            super::instant(event.metadata().name());
+        }
+
+        fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(TimelineName(name)) = span.extensions().get() {
+                    super::sys::duration_begin(name);
+                }
+            }
+        }
+
+        fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(TimelineName(name)) = span.extensions().get() {
+                    super::sys::duration_end(name);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sys::{Record, RECORDS};
+    use super::*;
+
+    /// Remove the records of the events named `names`, tests run in parallel and record other
+    /// events at the same time.
+    fn take_records(names: &[&CStr]) -> Vec<Record> {
+        let mut records = RECORDS.lock();
+        let (taken, other) = records.drain(..).partition(|record| match record {
+            Record::Begin(name) | Record::End(name) | Record::Instant(name) => names.contains(name),
+        });
+        *records = other;
+        taken
+    }
+
+    #[test]
+    fn test_events_use_interned_names() {
+        duration_begin("test_duration");
+        instant("test_instant");
+        duration_end("test_duration");
+
+        let duration = intern("test_duration");
+        let instant = intern("test_instant");
+        assert_eq!(
+            take_records(&[duration, instant]),
+            vec![
+                Record::Begin(duration),
+                Record::Instant(instant),
+                Record::End(duration),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intern_returns_cached_name() {
+        let first = intern("test_intern");
+        let second = intern("test_intern");
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert_eq!(first.to_str(), Ok("test_intern"));
+        assert_ne!(intern("test_intern_other").as_ptr(), first.as_ptr());
+    }
+
+    #[test]
+    fn test_intern_strips_nul() {
+        assert_eq!(intern("test\0nul").to_str(), Ok("testnul"));
+    }
+
+    #[cfg(feature = "tracing-layer")]
+    #[test]
+    fn test_layer_pairs_enter_and_exit() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(TimelineLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("outer");
+            let _outer = outer.enter();
+            {
+                let inner = tracing::info_span!("inner");
+                let _inner = inner.enter();
+            }
+            // a new span with the same name begins another duration with the same name
+            {
+                let inner = tracing::info_span!("inner");
+                let _inner = inner.enter();
+            }
+        });
+
+        let outer = intern("outer");
+        let inner = intern("inner");
+        assert_eq!(
+            take_records(&[outer, inner]),
+            vec![
+                Record::Begin(outer),
+                Record::Begin(inner),
+                Record::End(inner),
+                Record::Begin(inner),
+                Record::End(inner),
+                Record::End(outer),
+            ]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Integration with a tokio runtime, enabled by the `tokio` feature.
+//!
+//! Futures on tokio run on the runtime's threads, so they can't use the engine directly. Their
+//! results are posted back to the platform thread instead.
+//!
+//! async-std is still a default feature for `FlutterEngine::run_in_background`, so depend on
+//! flutter-engine with `default-features = false` to not start its runtime as well:
+//!
+//! ```toml
+//! flutter-engine = { version = "0.4", default-features = false, features = ["tokio"] }
+//! ```
+//!
+//! flutter-plugins and flutter-glfw forward the `async-std` and `tokio` features, so the same
+//! applies when depending on them.
+
+use crate::channel::{MethodCall, MethodError};
+use crate::{FlutterEngine, FlutterEngineWeakRef};
+use ::tokio::runtime::Handle;
+use ::tokio::task::JoinHandle;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+
+/// A handle to a tokio runtime which knows the engine its results are delivered to.
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    runtime: Handle,
+    engine: FlutterEngineWeakRef,
+}
+
+impl RuntimeHandle {
+    pub fn new(engine: &FlutterEngine, runtime: Handle) -> Self {
+        Self {
+            runtime,
+            engine: engine.downgrade(),
+        }
+    }
+
+    /// Use the runtime the current thread runs on. Panics if called outside of a tokio runtime.
+    pub fn current(engine: &FlutterEngine) -> Self {
+        Self::new(engine, Handle::current())
+    }
+
+    pub fn runtime(&self) -> &Handle {
+        &self.runtime
+    }
+
+    pub fn engine(&self) -> FlutterEngineWeakRef {
+        self.engine.clone()
+    }
+
+    /// Spawn a future on the runtime.
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.runtime.spawn(future)
+    }
+
+    /// Spawn a future on the runtime and pass its output to `f` on the platform thread. `f` is
+    /// not called if the engine was dropped in the meantime.
+    pub fn spawn_then<F, C>(&self, future: F, f: C) -> JoinHandle<()>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+        C: FnOnce(&FlutterEngine, F::Output) + Send + 'static,
+    {
+        let engine = self.engine.clone();
+        self.runtime.spawn(async move {
+            let output = future.await;
+            match engine.upgrade() {
+                Some(engine) => engine.run_on_platform_thread(move |engine| f(engine, output)),
+                None => warn!("Engine dropped before a future spawned on tokio completed"),
+            }
+        })
+    }
+
+    /// Answer a method call with the result of a future running on the runtime, e.g. from a
+    /// `MethodCallHandler`:
+    ///
+    /// ```ignore
+    /// fn on_method_call(&mut self, call: MethodCall) {
+    ///     self.handle.respond_with(call, async { Ok::<_, MethodError<()>>(fetch().await) });
+    /// }
+    /// ```
+    pub fn respond_with<F, T, D>(&self, call: MethodCall, future: F) -> JoinHandle<()>
+    where
+        F: Future<Output = Result<T, MethodError<D>>> + Send + 'static,
+        T: Serialize + Send + 'static,
+        D: Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.runtime.spawn(async move {
+            call.respond(future.await);
+        })
+    }
+}
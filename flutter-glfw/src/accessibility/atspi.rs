@@ -0,0 +1,227 @@
+//! Mapping of flutter semantics to AT-SPI roles, states and actions.
+
+use flutter_engine::semantics::{
+    SemanticsActions, SemanticsFlags, SemanticsNode, SemanticsRole, SemanticsTree,
+};
+
+// AtspiRole values used by the bridge
+pub const ROLE_CHECK_BOX: u32 = 7;
+pub const ROLE_IMAGE: u32 = 27;
+pub const ROLE_LABEL: u32 = 29;
+pub const ROLE_PANEL: u32 = 39;
+pub const ROLE_PASSWORD_TEXT: u32 = 40;
+pub const ROLE_PUSH_BUTTON: u32 = 43;
+pub const ROLE_RADIO_BUTTON: u32 = 44;
+pub const ROLE_SLIDER: u32 = 51;
+pub const ROLE_TOGGLE_BUTTON: u32 = 62;
+pub const ROLE_APPLICATION: u32 = 75;
+pub const ROLE_ENTRY: u32 = 79;
+pub const ROLE_HEADING: u32 = 83;
+pub const ROLE_LINK: u32 = 88;
+
+// AtspiStateType values used by the bridge
+pub const STATE_CHECKED: u32 = 4;
+pub const STATE_EDITABLE: u32 = 7;
+pub const STATE_ENABLED: u32 = 8;
+pub const STATE_FOCUSABLE: u32 = 11;
+pub const STATE_FOCUSED: u32 = 12;
+pub const STATE_PRESSED: u32 = 20;
+pub const STATE_SELECTED: u32 = 23;
+pub const STATE_SENSITIVE: u32 = 24;
+pub const STATE_SHOWING: u32 = 25;
+pub const STATE_SINGLE_LINE: u32 = 26;
+pub const STATE_VISIBLE: u32 = 30;
+pub const STATE_CHECKABLE: u32 = 41;
+pub const STATE_READ_ONLY: u32 = 43;
+
+/// States that are announced with a `StateChanged` event when they change.
+pub const NOTIFIED_STATES: &[(u32, &str)] = &[
+    (STATE_CHECKED, "checked"),
+    (STATE_ENABLED, "enabled"),
+    (STATE_FOCUSED, "focused"),
+    (STATE_PRESSED, "pressed"),
+    (STATE_SELECTED, "selected"),
+    (STATE_SENSITIVE, "sensitive"),
+];
+
+/// Standard actions exposed through the Action interface, with their AT-SPI names.
+/// Actions that need arguments or are only meaningful to the framework are left out.
+const ACTIONS: &[(SemanticsActions, &str, &str)] = &[
+    (SemanticsActions::TAP, "click", "Activate"),
+    (SemanticsActions::LONG_PRESS, "long-press", "Long press"),
+    (SemanticsActions::INCREASE, "increase", "Increase the value"),
+    (SemanticsActions::DECREASE, "decrease", "Decrease the value"),
+    (SemanticsActions::SCROLL_UP, "scroll-up", "Scroll up"),
+    (SemanticsActions::SCROLL_DOWN, "scroll-down", "Scroll down"),
+    (SemanticsActions::SCROLL_LEFT, "scroll-left", "Scroll left"),
+    (
+        SemanticsActions::SCROLL_RIGHT,
+        "scroll-right",
+        "Scroll right",
+    ),
+    (
+        SemanticsActions::SHOW_ON_SCREEN,
+        "show-on-screen",
+        "Scroll into view",
+    ),
+    (SemanticsActions::COPY, "copy", "Copy"),
+    (SemanticsActions::CUT, "cut", "Cut"),
+    (SemanticsActions::PASTE, "paste", "Paste"),
+    (SemanticsActions::DISMISS, "dismiss", "Dismiss"),
+];
+
+pub fn name(node: &SemanticsNode) -> String {
+    if node.value.is_empty() {
+        node.label.clone()
+    } else {
+        format!("{} {}", node.label, node.value)
+    }
+}
+
+pub fn role(node: &SemanticsNode) -> u32 {
+    match node.role() {
+        SemanticsRole::Button => ROLE_PUSH_BUTTON,
+        SemanticsRole::CheckBox => ROLE_CHECK_BOX,
+        SemanticsRole::RadioButton => ROLE_RADIO_BUTTON,
+        SemanticsRole::Switch => ROLE_TOGGLE_BUTTON,
+        SemanticsRole::TextField => {
+            if node.flags.contains(SemanticsFlags::IS_OBSCURED) {
+                ROLE_PASSWORD_TEXT
+            } else {
+                ROLE_ENTRY
+            }
+        }
+        SemanticsRole::Link => ROLE_LINK,
+        SemanticsRole::Header => ROLE_HEADING,
+        SemanticsRole::Image => ROLE_IMAGE,
+        SemanticsRole::Generic => {
+            if node
+                .actions
+                .intersects(SemanticsActions::INCREASE | SemanticsActions::DECREASE)
+            {
+                ROLE_SLIDER
+            } else if node.label.is_empty() {
+                ROLE_PANEL
+            } else {
+                ROLE_LABEL
+            }
+        }
+    }
+}
+
+pub fn role_name(role: u32) -> &'static str {
+    match role {
+        ROLE_CHECK_BOX => "check box",
+        ROLE_IMAGE => "image",
+        ROLE_LABEL => "label",
+        ROLE_PASSWORD_TEXT => "password text",
+        ROLE_PUSH_BUTTON => "push button",
+        ROLE_RADIO_BUTTON => "radio button",
+        ROLE_SLIDER => "slider",
+        ROLE_TOGGLE_BUTTON => "toggle button",
+        ROLE_APPLICATION => "application",
+        ROLE_ENTRY => "entry",
+        ROLE_HEADING => "heading",
+        ROLE_LINK => "link",
+        _ => "panel",
+    }
+}
+
+pub fn states(node: &SemanticsNode) -> Vec<u32> {
+    let flags = node.flags;
+    let mut states = Vec::new();
+    if !flags.contains(SemanticsFlags::IS_HIDDEN) {
+        states.push(STATE_VISIBLE);
+        states.push(STATE_SHOWING);
+    }
+    if !flags.contains(SemanticsFlags::HAS_ENABLED_STATE)
+        || flags.contains(SemanticsFlags::IS_ENABLED)
+    {
+        states.push(STATE_ENABLED);
+        states.push(STATE_SENSITIVE);
+    }
+    if flags.contains(SemanticsFlags::IS_FOCUSABLE) {
+        states.push(STATE_FOCUSABLE);
+    }
+    if flags.contains(SemanticsFlags::IS_FOCUSED) {
+        states.push(STATE_FOCUSED);
+    }
+    if flags.intersects(SemanticsFlags::HAS_CHECKED_STATE | SemanticsFlags::HAS_TOGGLED_STATE) {
+        states.push(STATE_CHECKABLE);
+    }
+    if flags.contains(SemanticsFlags::IS_CHECKED) {
+        states.push(STATE_CHECKED);
+    }
+    if flags.contains(SemanticsFlags::IS_TOGGLED) {
+        states.push(STATE_PRESSED);
+    }
+    if flags.contains(SemanticsFlags::IS_SELECTED) {
+        states.push(STATE_SELECTED);
+    }
+    if flags.contains(SemanticsFlags::IS_TEXT_FIELD) {
+        states.push(STATE_SINGLE_LINE);
+        if flags.contains(SemanticsFlags::IS_READ_ONLY) {
+            states.push(STATE_READ_ONLY);
+        } else {
+            states.push(STATE_EDITABLE);
+        }
+    }
+    states
+}
+
+/// Pack states into the two 32 bit words AT-SPI expects.
+pub fn state_set(states: &[u32]) -> Vec<u32> {
+    let mut set = vec![0, 0];
+    for state in states {
+        set[(state / 32) as usize] |= 1 << (state % 32);
+    }
+    set
+}
+
+pub enum Action {
+    Standard(SemanticsActions),
+    Custom(i32),
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    pub name: String,
+    pub description: String,
+}
+
+/// All actions of a node in the order they are exposed over AT-SPI.
+pub fn actions(tree: &SemanticsTree, node: &SemanticsNode) -> Vec<ActionInfo> {
+    let mut actions: Vec<ActionInfo> = ACTIONS
+        .iter()
+        .filter(|(action, _, _)| node.actions.contains(*action))
+        .map(|(action, name, description)| {
+            // the app may override the label of standard actions
+            let description = node
+                .custom_actions
+                .iter()
+                .filter_map(|id| tree.custom_action(*id))
+                .find(|custom| custom.override_action == *action)
+                .map_or_else(|| (*description).to_owned(), |custom| custom.label.clone());
+            ActionInfo {
+                action: Action::Standard(*action),
+                name: (*name).to_owned(),
+                description,
+            }
+        })
+        .collect();
+
+    if node.actions.contains(SemanticsActions::CUSTOM_ACTION) {
+        actions.extend(
+            node.custom_actions
+                .iter()
+                .filter_map(|id| tree.custom_action(*id))
+                .filter(|custom| custom.override_action.is_empty())
+                .map(|custom| ActionInfo {
+                    action: Action::Custom(custom.id),
+                    name: custom.label.clone(),
+                    description: custom.hint.clone(),
+                }),
+        );
+    }
+    actions
+}
@@ -0,0 +1,386 @@
+//! D-Bus objects implementing the AT-SPI interfaces.
+
+use super::{atspi, read_tree, SemanticsProvider};
+use flutter_engine::codec::Value;
+use flutter_engine::semantics::{SemanticsActions, SemanticsNode, SemanticsTree};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::{dbus_interface, fdo};
+
+pub const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+pub const NULL_PATH: &str = "/org/a11y/atspi/null";
+
+const ACCESSIBLE_INTERFACE: &str = "org.a11y.atspi.Accessible";
+const ACTION_INTERFACE: &str = "org.a11y.atspi.Action";
+const APPLICATION_INTERFACE: &str = "org.a11y.atspi.Application";
+
+/// A reference to an accessible object, as bus name and object path.
+pub type ObjectRef = (String, OwnedObjectPath);
+
+pub fn node_path(id: i32) -> String {
+    format!("/org/a11y/atspi/accessible/{}", id)
+}
+
+pub fn object_ref(bus_name: &str, path: &str) -> ObjectRef {
+    let path = ObjectPath::try_from(path.to_owned()).expect("Invalid object path");
+    (bus_name.to_owned(), OwnedObjectPath::from(path))
+}
+
+pub fn null_ref() -> ObjectRef {
+    object_ref("", NULL_PATH)
+}
+
+pub struct Context {
+    pub provider: Arc<dyn SemanticsProvider>,
+    pub bus_name: String,
+    /// The desktop object the application was embedded into.
+    pub parent: ObjectRef,
+    pub app_name: String,
+}
+
+impl Context {
+    fn node_ref(&self, id: i32) -> ObjectRef {
+        object_ref(&self.bus_name, &node_path(id))
+    }
+
+    fn root_ref(&self) -> ObjectRef {
+        object_ref(&self.bus_name, ROOT_PATH)
+    }
+
+    fn with_node<F, R>(&self, id: i32, f: F) -> fdo::Result<R>
+    where
+        F: FnOnce(&SemanticsTree, &SemanticsNode) -> R,
+    {
+        read_tree(&*self.provider, |tree| {
+            tree.node(id).map(|node| f(tree, node))
+        })
+        .flatten()
+        .ok_or_else(|| fdo::Error::UnknownObject(node_path(id)))
+    }
+}
+
+/// The application object registered with the desktop, its only child is the semantics root.
+pub struct ApplicationAccessible {
+    pub context: Arc<Context>,
+}
+
+impl ApplicationAccessible {
+    fn has_root(&self) -> bool {
+        read_tree(&*self.context.provider, |tree| tree.root().is_some()).unwrap_or(false)
+    }
+}
+
+#[dbus_interface(name = "org.a11y.atspi.Accessible")]
+impl ApplicationAccessible {
+    fn get_child_at_index(&self, index: i32) -> fdo::Result<ObjectRef> {
+        if index == 0 && self.has_root() {
+            Ok(self.context.node_ref(SemanticsTree::ROOT_ID))
+        } else {
+            Err(fdo::Error::InvalidArgs(format!(
+                "No child at index {}",
+                index
+            )))
+        }
+    }
+
+    fn get_children(&self) -> Vec<ObjectRef> {
+        if self.has_root() {
+            vec![self.context.node_ref(SemanticsTree::ROOT_ID)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn get_index_in_parent(&self) -> i32 {
+        -1
+    }
+
+    fn get_relation_set(&self) -> Vec<(u32, Vec<ObjectRef>)> {
+        vec![]
+    }
+
+    fn get_role(&self) -> u32 {
+        atspi::ROLE_APPLICATION
+    }
+
+    fn get_role_name(&self) -> String {
+        atspi::role_name(atspi::ROLE_APPLICATION).to_owned()
+    }
+
+    fn get_localized_role_name(&self) -> String {
+        self.get_role_name()
+    }
+
+    fn get_state(&self) -> Vec<u32> {
+        atspi::state_set(&[])
+    }
+
+    fn get_attributes(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    fn get_application(&self) -> ObjectRef {
+        self.context.root_ref()
+    }
+
+    fn get_interfaces(&self) -> Vec<String> {
+        vec![
+            ACCESSIBLE_INTERFACE.to_owned(),
+            APPLICATION_INTERFACE.to_owned(),
+        ]
+    }
+
+    #[dbus_interface(property, name = "Name")]
+    fn accessible_name(&self) -> String {
+        self.context.app_name.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn description(&self) -> String {
+        String::new()
+    }
+
+    #[dbus_interface(property)]
+    fn parent(&self) -> ObjectRef {
+        self.context.parent.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn child_count(&self) -> i32 {
+        if self.has_root() {
+            1
+        } else {
+            0
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn locale(&self) -> String {
+        String::new()
+    }
+
+    #[dbus_interface(property)]
+    fn accessible_id(&self) -> String {
+        String::new()
+    }
+}
+
+pub struct Application {
+    pub id: i32,
+}
+
+#[dbus_interface(name = "org.a11y.atspi.Application")]
+impl Application {
+    fn get_locale(&self, _lctype: u32) -> String {
+        String::new()
+    }
+
+    #[dbus_interface(property)]
+    fn toolkit_name(&self) -> String {
+        "flutter-rs".to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn atspi_version(&self) -> String {
+        "2.1".to_owned()
+    }
+
+    #[dbus_interface(property)]
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    #[dbus_interface(property)]
+    fn set_id(&mut self, id: i32) {
+        self.id = id;
+    }
+}
+
+pub struct NodeAccessible {
+    pub context: Arc<Context>,
+    pub id: i32,
+}
+
+#[dbus_interface(name = "org.a11y.atspi.Accessible")]
+impl NodeAccessible {
+    fn get_child_at_index(&self, index: i32) -> fdo::Result<ObjectRef> {
+        let child = self.context.with_node(self.id, |_, node| {
+            node.children_in_traversal_order
+                .get(index as usize)
+                .copied()
+        })?;
+        child
+            .map(|id| self.context.node_ref(id))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("No child at index {}", index)))
+    }
+
+    fn get_children(&self) -> fdo::Result<Vec<ObjectRef>> {
+        self.context.with_node(self.id, |_, node| {
+            node.children_in_traversal_order
+                .iter()
+                .map(|id| self.context.node_ref(*id))
+                .collect()
+        })
+    }
+
+    fn get_index_in_parent(&self) -> fdo::Result<i32> {
+        self.context
+            .with_node(self.id, |tree, node| match tree.parent(node) {
+                Some(parent) => parent
+                    .children_in_traversal_order
+                    .iter()
+                    .position(|id| *id == node.id)
+                    .map_or(-1, |index| index as i32),
+                None => 0,
+            })
+    }
+
+    fn get_relation_set(&self) -> Vec<(u32, Vec<ObjectRef>)> {
+        vec![]
+    }
+
+    fn get_role(&self) -> fdo::Result<u32> {
+        self.context.with_node(self.id, |_, node| atspi::role(node))
+    }
+
+    fn get_role_name(&self) -> fdo::Result<String> {
+        self.context.with_node(self.id, |_, node| {
+            atspi::role_name(atspi::role(node)).to_owned()
+        })
+    }
+
+    fn get_localized_role_name(&self) -> fdo::Result<String> {
+        self.get_role_name()
+    }
+
+    fn get_state(&self) -> fdo::Result<Vec<u32>> {
+        self.context
+            .with_node(self.id, |_, node| atspi::state_set(&atspi::states(node)))
+    }
+
+    fn get_attributes(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    fn get_application(&self) -> ObjectRef {
+        self.context.root_ref()
+    }
+
+    fn get_interfaces(&self) -> Vec<String> {
+        vec![ACCESSIBLE_INTERFACE.to_owned(), ACTION_INTERFACE.to_owned()]
+    }
+
+    #[dbus_interface(property, name = "Name")]
+    fn accessible_name(&self) -> String {
+        self.context
+            .with_node(self.id, |_, node| atspi::name(node))
+            .unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn description(&self) -> String {
+        self.context
+            .with_node(self.id, |_, node| node.hint.clone())
+            .unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn parent(&self) -> ObjectRef {
+        self.context
+            .with_node(self.id, |tree, node| match tree.parent(node) {
+                Some(parent) => self.context.node_ref(parent.id),
+                None => self.context.root_ref(),
+            })
+            .unwrap_or_else(|_| null_ref())
+    }
+
+    #[dbus_interface(property)]
+    fn child_count(&self) -> i32 {
+        self.context
+            .with_node(self.id, |_, node| {
+                node.children_in_traversal_order.len() as i32
+            })
+            .unwrap_or(0)
+    }
+
+    #[dbus_interface(property)]
+    fn locale(&self) -> String {
+        String::new()
+    }
+
+    #[dbus_interface(property)]
+    fn accessible_id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+pub struct NodeAction {
+    pub context: Arc<Context>,
+    pub id: i32,
+}
+
+impl NodeAction {
+    fn with_action<F, R>(&self, index: i32, f: F) -> fdo::Result<R>
+    where
+        F: FnOnce(&atspi::ActionInfo) -> R,
+    {
+        self.context
+            .with_node(self.id, |tree, node| {
+                atspi::actions(tree, node).get(index as usize).map(f)
+            })?
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("No action at index {}", index)))
+    }
+}
+
+#[dbus_interface(name = "org.a11y.atspi.Action")]
+impl NodeAction {
+    fn get_description(&self, index: i32) -> fdo::Result<String> {
+        self.with_action(index, |action| action.description.clone())
+    }
+
+    fn get_name(&self, index: i32) -> fdo::Result<String> {
+        self.with_action(index, |action| action.name.clone())
+    }
+
+    fn get_localized_name(&self, index: i32) -> fdo::Result<String> {
+        self.get_name(index)
+    }
+
+    fn get_key_binding(&self, _index: i32) -> String {
+        String::new()
+    }
+
+    fn get_actions(&self) -> fdo::Result<Vec<(String, String, String)>> {
+        self.context.with_node(self.id, |tree, node| {
+            atspi::actions(tree, node)
+                .into_iter()
+                .map(|action| (action.name, action.description, String::new()))
+                .collect()
+        })
+    }
+
+    fn do_action(&self, index: i32) -> fdo::Result<bool> {
+        let (action, args) = self.with_action(index, |action| match action.action {
+            atspi::Action::Standard(action) => (action, None),
+            atspi::Action::Custom(id) => (SemanticsActions::CUSTOM_ACTION, Some(Value::I32(id))),
+        })?;
+        self.context.provider.dispatch_action(self.id, action, args);
+        Ok(true)
+    }
+
+    #[dbus_interface(property)]
+    fn n_actions(&self) -> i32 {
+        self.context
+            .with_node(self.id, |tree, node| {
+                atspi::actions(tree, node).len() as i32
+            })
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,441 @@
+//! Exports the semantics tree of an engine to screen readers over AT-SPI2.
+//!
+//! The bridge serves the accessibility bus from a background thread. Changes to the tree are
+//! picked up by polling, so they are announced with a short delay.
+
+mod atspi;
+mod interfaces;
+
+use self::interfaces::{
+    node_path, null_ref, object_ref, ApplicationAccessible, Context, NodeAccessible, NodeAction,
+    ObjectRef, ROOT_PATH,
+};
+use flutter_engine::codec::Value;
+use flutter_engine::semantics::{SemanticsActions, SemanticsTree};
+use flutter_engine::{FlutterEngine, FlutterEngineWeakRef};
+use log::{debug, error, warn};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use zbus::zvariant::{self, ObjectPath};
+use zbus::{Connection, ObjectServer};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the bridge waits for the platform thread to hand over the semantics tree.
+const TREE_TIMEOUT: Duration = Duration::from_secs(1);
+const EVENT_INTERFACE: &str = "org.a11y.atspi.Event.Object";
+
+/// Source of the semantics tree exported by the bridge.
+pub trait SemanticsProvider: Send + Sync {
+    /// Call `f` with the current tree. `f` is not called if the tree is no longer available.
+    fn with_tree(&self, f: &mut dyn FnMut(&SemanticsTree));
+
+    /// Call `f` with the current tree, unless it still has `generation`.
+    fn with_changed_tree(&self, generation: Option<u64>, f: &mut dyn FnMut(&SemanticsTree)) {
+        self.with_tree(&mut |tree| {
+            if Some(tree.generation()) != generation {
+                f(tree)
+            }
+        });
+    }
+
+    fn dispatch_action(&self, node_id: i32, action: SemanticsActions, args: Option<Value>);
+}
+
+/// The tree is copied on the platform thread, so no handle of the engine is kept on the bridge
+/// thread.
+impl SemanticsProvider for FlutterEngineWeakRef {
+    fn with_tree(&self, f: &mut dyn FnMut(&SemanticsTree)) {
+        self.with_changed_tree(None, f)
+    }
+
+    /// Only copies the tree if it changed, as the bridge polls for changes.
+    fn with_changed_tree(&self, generation: Option<u64>, f: &mut dyn FnMut(&SemanticsTree)) {
+        let (tx, rx) = mpsc::channel();
+        let posted = self.run_on_platform_thread(move |engine| {
+            let tree = engine.with_semantics_tree(|tree| {
+                if Some(tree.generation()) != generation {
+                    Some(tree.clone())
+                } else {
+                    None
+                }
+            });
+            let _ = tx.send(tree);
+        });
+        if !posted {
+            return;
+        }
+        match rx.recv_timeout(TREE_TIMEOUT) {
+            Ok(Some(tree)) => f(&tree),
+            Ok(None) => {}
+            Err(_) => debug!("Semantics tree not received from the platform thread"),
+        }
+    }
+
+    fn dispatch_action(&self, node_id: i32, action: SemanticsActions, args: Option<Value>) {
+        self.run_on_platform_thread(move |engine| {
+            if let Err(err) = engine
+                .dispatch_semantics_action(node_id, action, args.as_ref())
+                .wait()
+            {
+                warn!("Failed to dispatch semantics action: {}", err);
+            }
+        });
+    }
+}
+
+fn read_tree<F, R>(provider: &dyn SemanticsProvider, f: F) -> Option<R>
+where
+    F: FnOnce(&SemanticsTree) -> R,
+{
+    read_changed_tree(provider, None, f)
+}
+
+fn read_changed_tree<F, R>(
+    provider: &dyn SemanticsProvider,
+    generation: Option<u64>,
+    f: F,
+) -> Option<R>
+where
+    F: FnOnce(&SemanticsTree) -> R,
+{
+    let mut f = Some(f);
+    let mut result = None;
+    provider.with_changed_tree(generation, &mut |tree| {
+        if let Some(f) = f.take() {
+            result = Some(f(tree));
+        }
+    });
+    result
+}
+
+/// Keeps the bridge running until dropped.
+pub struct AccessibilityBridge {
+    bus_name: String,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AccessibilityBridge {
+    /// Enable semantics on the engine and register it as application `app_name` with the
+    /// accessibility bus of the current session.
+    pub fn start(engine: &FlutterEngine, app_name: &str) -> zbus::Result<Self> {
+        let session = Connection::new_session()?;
+        let reply = session.call_method(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            Some("org.a11y.Bus"),
+            "GetAddress",
+            &(),
+        )?;
+        let address: String = reply.body()?;
+        let connection = Connection::new_for_address(&address, true)?;
+
+        let bus_name = unique_name(&connection)?;
+        let reply = connection.call_method(
+            Some("org.a11y.atspi.Registry"),
+            ROOT_PATH,
+            Some("org.a11y.atspi.Socket"),
+            "Embed",
+            &object_ref(&bus_name, ROOT_PATH),
+        )?;
+        let parent: ObjectRef = reply.body()?;
+
+        if let Err(err) = engine.update_semantics_enabled(true).wait() {
+            warn!("Failed to enable semantics: {}", err);
+        }
+        Self::spawn(connection, Arc::new(engine.downgrade()), parent, app_name)
+    }
+
+    /// Serve the tree of `provider` on the bus at `address` without registering with the AT-SPI
+    /// registry, e.g. to inspect the exported objects on a private bus.
+    pub fn start_on_bus(
+        address: &str,
+        provider: Arc<dyn SemanticsProvider>,
+        app_name: &str,
+    ) -> zbus::Result<Self> {
+        let connection = Connection::new_for_address(address, true)?;
+        Self::spawn(connection, provider, null_ref(), app_name)
+    }
+
+    /// The unique name of the bridge's connection, which owns all exported objects.
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    fn spawn(
+        connection: Connection,
+        provider: Arc<dyn SemanticsProvider>,
+        parent: ObjectRef,
+        app_name: &str,
+    ) -> zbus::Result<Self> {
+        let bus_name = unique_name(&connection)?;
+        let context = Arc::new(Context {
+            provider,
+            bus_name: bus_name.clone(),
+            parent,
+            app_name: app_name.to_owned(),
+        });
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        // the object server can't be moved between threads, so it is set up on the bridge thread
+        let (setup_tx, setup_rx) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("atspi-bridge".to_owned())
+            .spawn(move || match Server::new(connection, context) {
+                Ok(server) => {
+                    let _ = setup_tx.send(Ok(()));
+                    server.run(&thread_running);
+                }
+                Err(err) => {
+                    let _ = setup_tx.send(Err(err));
+                }
+            })?;
+        setup_rx.recv().unwrap_or(Err(zbus::Error::Unsupported))?;
+
+        Ok(Self {
+            bus_name,
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Stops the bridge and waits for its thread, which notices within the poll interval, or the
+/// tree timeout when dropped on the platform thread.
+impl Drop for AccessibilityBridge {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("AT-SPI bridge thread panicked");
+            }
+        }
+    }
+}
+
+fn unique_name(connection: &Connection) -> zbus::Result<String> {
+    connection
+        .unique_name()
+        .map(str::to_owned)
+        .ok_or(zbus::Error::Unsupported)
+}
+
+fn path(path: &str) -> ObjectPath<'_> {
+    ObjectPath::try_from(path).expect("Invalid object path")
+}
+
+/// What was last announced about a node, used to find out which events to emit.
+struct NodeSnapshot {
+    name: String,
+    children: Vec<i32>,
+    states: Vec<u32>,
+}
+
+struct Server {
+    objects: ObjectServer<'static>,
+    connection: Connection,
+    context: Arc<Context>,
+    nodes: HashMap<i32, NodeSnapshot>,
+    generation: Option<u64>,
+}
+
+impl Server {
+    fn new(connection: Connection, context: Arc<Context>) -> zbus::Result<Self> {
+        let mut objects = ObjectServer::new(&connection);
+        objects.at(
+            &path(ROOT_PATH),
+            ApplicationAccessible {
+                context: context.clone(),
+            },
+        )?;
+        objects.at(&path(ROOT_PATH), interfaces::Application { id: 0 })?;
+
+        Ok(Self {
+            objects,
+            connection,
+            context,
+            nodes: HashMap::new(),
+            generation: None,
+        })
+    }
+
+    fn run(mut self, running: &AtomicBool) {
+        debug!("AT-SPI bridge serving as {}", self.context.bus_name);
+        while running.load(Ordering::Relaxed) {
+            if wait_readable(&self.connection, POLL_INTERVAL) {
+                if let Err(err) = self.objects.try_handle_next() {
+                    error!("Failed to handle AT-SPI request: {}", err);
+                    if let zbus::Error::Io(_) = err {
+                        break;
+                    }
+                }
+            }
+            if let Err(err) = self.sync() {
+                warn!("Failed to update AT-SPI objects: {}", err);
+            }
+        }
+        debug!("AT-SPI bridge stopped");
+    }
+
+    /// Register objects for new nodes, remove objects of removed nodes and announce changes.
+    ///
+    /// The generation is only recorded once everything succeeded, so a failed update is retried
+    /// on the next poll.
+    fn sync(&mut self) -> zbus::Result<()> {
+        let context = self.context.clone();
+        let snapshot = read_changed_tree(&*context.provider, self.generation, |tree| {
+            let nodes: HashMap<i32, NodeSnapshot> = tree
+                .iter()
+                .map(|node| {
+                    let snapshot = NodeSnapshot {
+                        name: atspi::name(node),
+                        children: node.children_in_traversal_order.clone(),
+                        states: atspi::states(node),
+                    };
+                    (node.id, snapshot)
+                })
+                .collect();
+            (tree.generation(), nodes)
+        });
+        let (generation, nodes) = match snapshot {
+            Some(snapshot) => snapshot,
+            None => return Ok(()),
+        };
+
+        let root_changed = nodes.contains_key(&SemanticsTree::ROOT_ID)
+            != self.nodes.contains_key(&SemanticsTree::ROOT_ID);
+
+        for id in self.nodes.keys().filter(|id| !nodes.contains_key(id)) {
+            let node_path = node_path(*id);
+            remove_object::<NodeAction>(&mut self.objects, &node_path)?;
+            remove_object::<NodeAccessible>(&mut self.objects, &node_path)?;
+            self.emit_state_changed(*id, "defunct", true)?;
+        }
+
+        for (id, node) in nodes.iter() {
+            let old = match self.nodes.get(id) {
+                Some(old) => old,
+                None => {
+                    let node_path = node_path(*id);
+                    self.objects.at(
+                        &path(&node_path),
+                        NodeAccessible {
+                            context: context.clone(),
+                            id: *id,
+                        },
+                    )?;
+                    self.objects.at(
+                        &path(&node_path),
+                        NodeAction {
+                            context: context.clone(),
+                            id: *id,
+                        },
+                    )?;
+                    continue;
+                }
+            };
+
+            if old.name != node.name {
+                self.emit_property_change(*id, "accessible-name", &node.name)?;
+            }
+            for (index, child) in old.children.iter().enumerate() {
+                if !node.children.contains(child) {
+                    self.emit_children_changed(&node_path(*id), "remove", index, *child)?;
+                }
+            }
+            for (index, child) in node.children.iter().enumerate() {
+                if !old.children.contains(child) {
+                    self.emit_children_changed(&node_path(*id), "add", index, *child)?;
+                }
+            }
+            for (state, name) in atspi::NOTIFIED_STATES {
+                let was_set = old.states.contains(state);
+                let is_set = node.states.contains(state);
+                if was_set != is_set {
+                    self.emit_state_changed(*id, name, is_set)?;
+                }
+            }
+        }
+
+        if root_changed {
+            let detail = if nodes.contains_key(&SemanticsTree::ROOT_ID) {
+                "add"
+            } else {
+                "remove"
+            };
+            self.emit_children_changed(ROOT_PATH, detail, 0, SemanticsTree::ROOT_ID)?;
+        }
+
+        self.nodes = nodes;
+        self.generation = Some(generation);
+        Ok(())
+    }
+
+    fn emit_state_changed(&self, id: i32, state: &str, is_set: bool) -> zbus::Result<()> {
+        let path = node_path(id);
+        self.emit(&path, "StateChanged", state, is_set as i32, 0.into())
+    }
+
+    fn emit_property_change(&self, id: i32, property: &str, value: &str) -> zbus::Result<()> {
+        let path = node_path(id);
+        self.emit(&path, "PropertyChange", property, 0, value.into())
+    }
+
+    fn emit_children_changed(
+        &self,
+        path: &str,
+        detail: &str,
+        index: usize,
+        child: i32,
+    ) -> zbus::Result<()> {
+        let child = object_ref(&self.context.bus_name, &node_path(child));
+        self.emit(path, "ChildrenChanged", detail, index as i32, child.into())
+    }
+
+    fn emit(
+        &self,
+        path: &str,
+        signal: &str,
+        detail: &str,
+        detail1: i32,
+        data: zvariant::Value,
+    ) -> zbus::Result<()> {
+        let properties: HashMap<String, zvariant::Value> = HashMap::new();
+        self.connection.emit_signal(
+            None,
+            path,
+            EVENT_INTERFACE,
+            signal,
+            &(detail, detail1, 0, data, properties),
+        )
+    }
+}
+
+/// Remove the interface `I` of the object at `node_path`. Interfaces already removed by a
+/// previously failed update are skipped.
+fn remove_object<I: zbus::Interface>(
+    objects: &mut ObjectServer<'static>,
+    node_path: &str,
+) -> zbus::Result<()> {
+    match objects.remove::<I>(&path(node_path)) {
+        Ok(_) | Err(zbus::Error::InterfaceNotFound) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Wait until the connection has data to read. zbus only offers blocking reads, which would
+/// keep the thread from picking up changes to the tree.
+fn wait_readable(connection: &Connection, timeout: Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: connection.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) > 0 }
+}
@@ -0,0 +1,245 @@
+//! Preferences of the Linux desktop, read from GNOME's gsettings.
+
+use flutter_engine::semantics::AccessibilityFeatures;
+use log::{debug, warn};
+use parking_lot::Mutex;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+
+const INTERFACE_SCHEMA: &str = "org.gnome.desktop.interface";
+const A11Y_APPLICATIONS_SCHEMA: &str = "org.gnome.desktop.a11y.applications";
+const A11Y_INTERFACE_SCHEMA: &str = "org.gnome.desktop.a11y.interface";
+
+/// Schemas that contain keys used by `DesktopSettings`.
+const SCHEMAS: &[&str] = &[
+    INTERFACE_SCHEMA,
+    A11Y_APPLICATIONS_SCHEMA,
+    A11Y_INTERFACE_SCHEMA,
+];
+
+/// The preferences of the desktop which are sent to the engine.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DesktopSettings {
+    /// High contrast is sent as `BOLD_TEXT`, the embedder has no flag for it and bold text is
+    /// the closest it offers.
+    pub accessibility_features: AccessibilityFeatures,
+    pub text_scale_factor: f64,
+    pub use_24_hour_format: bool,
+    pub dark_theme: bool,
+}
+
+/// The values of the keys in `SCHEMAS`, as far as they are known.
+#[derive(Clone, Debug)]
+struct Keys {
+    enable_animations: bool,
+    screen_reader_enabled: bool,
+    high_contrast: bool,
+    text_scaling_factor: f64,
+    clock_format_24h: bool,
+    color_scheme_dark: bool,
+    gtk_theme_dark: bool,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            enable_animations: true,
+            screen_reader_enabled: false,
+            high_contrast: false,
+            text_scaling_factor: 1.0,
+            clock_format_24h: false,
+            color_scheme_dark: false,
+            gtk_theme_dark: false,
+        }
+    }
+}
+
+impl Keys {
+    /// Update a key from its value as printed by gsettings. Unknown keys and values which can't be
+    /// parsed are ignored.
+    fn set(&mut self, schema: &str, key: &str, value: &str) {
+        let value = value.trim();
+        // strings are printed quoted
+        let string = value.trim_matches('\'');
+        match (schema, key) {
+            (INTERFACE_SCHEMA, "enable-animations") => self.enable_animations = value == "true",
+            (INTERFACE_SCHEMA, "text-scaling-factor") => {
+                if let Ok(factor) = value.parse() {
+                    self.text_scaling_factor = factor;
+                }
+            }
+            (INTERFACE_SCHEMA, "clock-format") => self.clock_format_24h = string == "24h",
+            (INTERFACE_SCHEMA, "color-scheme") => self.color_scheme_dark = string == "prefer-dark",
+            (INTERFACE_SCHEMA, "gtk-theme") => {
+                self.gtk_theme_dark = string.to_lowercase().ends_with("-dark")
+            }
+            (A11Y_APPLICATIONS_SCHEMA, "screen-reader-enabled") => {
+                self.screen_reader_enabled = value == "true"
+            }
+            (A11Y_INTERFACE_SCHEMA, "high-contrast") => self.high_contrast = value == "true",
+            _ => {}
+        }
+    }
+
+    fn settings(&self) -> DesktopSettings {
+        let mut features = AccessibilityFeatures::empty();
+        if !self.enable_animations {
+            features |=
+                AccessibilityFeatures::DISABLE_ANIMATIONS | AccessibilityFeatures::REDUCE_MOTION;
+        }
+        if self.screen_reader_enabled {
+            features |= AccessibilityFeatures::ACCESSIBLE_NAVIGATION;
+        }
+        if self.high_contrast {
+            features |= AccessibilityFeatures::BOLD_TEXT;
+        }
+        DesktopSettings {
+            accessibility_features: features,
+            text_scale_factor: self.text_scaling_factor,
+            use_24_hour_format: self.clock_format_24h,
+            dark_theme: self.color_scheme_dark || self.gtk_theme_dark,
+        }
+    }
+}
+
+/// Read all keys of `schema`, one `gsettings` process per schema.
+fn read_schema(keys: &mut Keys, schema: &str) {
+    let output = match Command::new("gsettings")
+        .arg("list-recursively")
+        .arg(schema)
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return,
+    };
+    // every line is `<schema> <key> <value>`
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut parts = line.splitn(3, ' ');
+        if let (Some(schema), Some(key), Some(value)) = (parts.next(), parts.next(), parts.next()) {
+            keys.set(schema, key, value);
+        }
+    }
+}
+
+/// Reads the preferences and calls a callback whenever they change, until dropped.
+pub struct SettingsWatcher {
+    monitors: Vec<Child>,
+    state: Arc<Mutex<(DesktopSettings, Keys)>>,
+}
+
+impl SettingsWatcher {
+    /// Settings that can't be read keep their defaults.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(DesktopSettings) + Send + Sync + 'static,
+    {
+        let mut keys = Keys::default();
+        for schema in SCHEMAS {
+            read_schema(&mut keys, schema);
+        }
+        let state = Arc::new(Mutex::new((keys.settings(), keys)));
+        let callback = Arc::new(callback);
+        let mut monitors = Vec::new();
+        for schema in SCHEMAS {
+            let mut child = match Command::new("gsettings")
+                .arg("monitor")
+                .arg(schema)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                Ok(child) => child,
+                Err(err) => {
+                    warn!("Failed to watch desktop settings: {}", err);
+                    break;
+                }
+            };
+
+            let stdout = child.stdout.take().unwrap();
+            let state = state.clone();
+            let callback = callback.clone();
+            thread::spawn(move || {
+                // every line is `<key>: <value>` of a changed key, the monitor exits when killed
+                for line in BufReader::new(stdout).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    let mut parts = line.splitn(2, ": ");
+                    let (key, value) = match (parts.next(), parts.next()) {
+                        (Some(key), Some(value)) => (key, value),
+                        _ => continue,
+                    };
+                    let settings = {
+                        let mut state = state.lock();
+                        let (sent, keys) = &mut *state;
+                        keys.set(schema, key, value);
+                        let settings = keys.settings();
+                        if *sent == settings {
+                            continue;
+                        }
+                        *sent = settings;
+                        settings
+                    };
+                    debug!("Desktop settings changed: {:?}", settings);
+                    callback(settings);
+                }
+            });
+            monitors.push(child);
+        }
+        Self { monitors, state }
+    }
+
+    /// The current preferences.
+    pub fn settings(&self) -> DesktopSettings {
+        self.state.lock().0
+    }
+}
+
+impl Drop for SettingsWatcher {
+    fn drop(&mut self) {
+        for monitor in &mut self.monitors {
+            let _ = monitor.kill();
+            let _ = monitor.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        let mut keys = Keys::default();
+        keys.set(INTERFACE_SCHEMA, "enable-animations", "false");
+        keys.set(INTERFACE_SCHEMA, "text-scaling-factor", "1.25");
+        keys.set(INTERFACE_SCHEMA, "clock-format", "'24h'");
+        keys.set(INTERFACE_SCHEMA, "gtk-theme", "'Adwaita-dark'");
+        keys.set(A11Y_INTERFACE_SCHEMA, "high-contrast", "true");
+        keys.set(A11Y_APPLICATIONS_SCHEMA, "screen-reader-enabled", "false");
+        keys.set(A11Y_APPLICATIONS_SCHEMA, "unknown-key", "true");
+
+        assert_eq!(
+            keys.settings(),
+            DesktopSettings {
+                accessibility_features: AccessibilityFeatures::DISABLE_ANIMATIONS
+                    | AccessibilityFeatures::REDUCE_MOTION
+                    | AccessibilityFeatures::BOLD_TEXT,
+                text_scale_factor: 1.25,
+                use_24_hour_format: true,
+                dark_theme: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_values_are_ignored() {
+        let mut keys = Keys::default();
+        keys.set(INTERFACE_SCHEMA, "text-scaling-factor", "large");
+        assert_eq!(keys.settings().text_scale_factor, 1.0);
+    }
+}
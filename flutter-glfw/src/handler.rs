@@ -1,5 +1,5 @@
 use flutter_engine::tasks::TaskRunnerHandler;
-use flutter_engine::FlutterOpenGLHandler;
+use flutter_engine::{FlutterEngine, FlutterEngineWeakRef, FlutterOpenGLHandler, VsyncHandler};
 use flutter_plugins::platform::{AppSwitcherDescription, MimeError, PlatformHandler};
 use flutter_plugins::textinput::TextInputHandler;
 use flutter_plugins::window::{PositionParams, WindowHandler};
@@ -7,6 +7,7 @@ use glfw::Context;
 use parking_lot::Mutex;
 use std::cell::RefCell;
 use std::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
 pub(crate) struct GlfwPlatformTaskHandler {}
@@ -25,6 +26,38 @@ impl TaskRunnerHandler for GlfwPlatformTaskHandler {
     }
 }
 
+/// glfw does not expose the timing of the display's vsync, so frames are scheduled on a fixed grid
+/// of the monitor's refresh interval instead.
+pub(crate) struct GlfwVsyncHandler {
+    refresh_rate: AtomicU32,
+}
+
+impl GlfwVsyncHandler {
+    pub fn new(refresh_rate: u32) -> Self {
+        Self {
+            refresh_rate: AtomicU32::new(refresh_rate),
+        }
+    }
+
+    pub fn set_refresh_rate(&self, refresh_rate: u32) {
+        self.refresh_rate.store(refresh_rate, Ordering::Relaxed);
+    }
+}
+
+impl VsyncHandler for GlfwVsyncHandler {
+    fn request_frame_callback(&self, engine: FlutterEngineWeakRef, baton: isize) {
+        let refresh_rate = self.refresh_rate.load(Ordering::Relaxed).max(1);
+        let interval = 1_000_000_000 / u64::from(refresh_rate);
+        let now = FlutterEngine::current_time();
+        let frame_start = now - now % interval + interval;
+        if let Some(engine) = engine.upgrade() {
+            // this runs on an engine thread, which must not wait for the platform thread. The
+            // call only fails once the engine is shutting down and no longer needs the baton.
+            drop(engine.on_vsync(baton, frame_start, frame_start + interval));
+        }
+    }
+}
+
 pub(crate) struct GlfwOpenGLHandler {
     render_ctx: RefCell<glfw::RenderContext>,
     resource_ctx: RefCell<glfw::RenderContext>,
@@ -1,7 +1,12 @@
 use crate::window::{CreateError, FlutterWindow, WindowArgs};
+use flutter_engine::aot::AotData;
 use log::error;
 use std::path::PathBuf;
 
+#[cfg(feature = "accessibility")]
+pub mod accessibility;
+#[cfg(target_os = "linux")]
+mod desktop_settings;
 mod handler;
 pub mod window;
 
@@ -29,7 +34,24 @@ impl FlutterDesktop {
         assets_path: PathBuf,
         arguments: Vec<String>,
     ) -> Result<FlutterWindow, CreateError> {
-        FlutterWindow::create(&mut self.glfw, window_args, assets_path, arguments)
+        FlutterWindow::create(&mut self.glfw, window_args, assets_path, arguments, None)
+    }
+
+    /// Create a window for a release or profile engine, which runs the given AOT compiled code.
+    pub fn create_window_with_aot_data(
+        &mut self,
+        window_args: &WindowArgs,
+        assets_path: PathBuf,
+        arguments: Vec<String>,
+        aot_data: AotData,
+    ) -> Result<FlutterWindow, CreateError> {
+        FlutterWindow::create(
+            &mut self.glfw,
+            window_args,
+            assets_path,
+            arguments,
+            Some(aot_data),
+        )
     }
 
     pub fn glfw(&self) -> glfw::Glfw {
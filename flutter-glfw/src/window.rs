@@ -1,12 +1,16 @@
+#[cfg(target_os = "linux")]
+use crate::desktop_settings::{DesktopSettings, SettingsWatcher};
 use crate::handler::{
     GlfwOpenGLHandler, GlfwPlatformHandler, GlfwPlatformTaskHandler, GlfwTextInputHandler,
-    GlfwWindowHandler,
+    GlfwVsyncHandler, GlfwWindowHandler,
 };
+use flutter_engine::aot::AotData;
 use flutter_engine::builder::FlutterEngineBuilder;
 use flutter_engine::channel::Channel;
+use flutter_engine::error::EngineError;
 use flutter_engine::ffi::{
     FlutterPointerDeviceKind, FlutterPointerMouseButtons, FlutterPointerPhase,
-    FlutterPointerSignalKind,
+    FlutterPointerSignalKind, Locale, PointerEvent,
 };
 use flutter_engine::plugins::{Plugin, PluginRegistrar};
 use flutter_engine::tasks::TaskRunnerHandler;
@@ -16,16 +20,17 @@ use flutter_plugins::dialog::DialogPlugin;
 use flutter_plugins::isolate::IsolatePlugin;
 use flutter_plugins::keyevent::{KeyAction, KeyActionType, KeyEventPlugin};
 use flutter_plugins::lifecycle::LifecyclePlugin;
-use flutter_plugins::localization::LocalizationPlugin;
+use flutter_plugins::localization::{self, LocalizationPlugin};
 use flutter_plugins::navigation::NavigationPlugin;
 use flutter_plugins::platform::PlatformPlugin;
 use flutter_plugins::settings::SettingsPlugin;
 use flutter_plugins::system::SystemPlugin;
 use flutter_plugins::textinput::TextInputPlugin;
 use flutter_plugins::window::WindowPlugin;
-use log::{debug, info};
+use log::{debug, error, info};
 use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::mem;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::{Receiver, SendError, Sender};
@@ -39,29 +44,39 @@ const BY_WORD_MODIFIER_KEY: glfw::Modifiers = glfw::Modifiers::Control;
 #[cfg(target_os = "macos")]
 const BY_WORD_MODIFIER_KEY: glfw::Modifiers = glfw::Modifiers::Alt;
 const SELECT_MODIFIER_KEY: glfw::Modifiers = glfw::Modifiers::Shift;
+// used if the refresh rate of the monitor can't be determined
+const DEFAULT_REFRESH_RATE: u32 = 60;
 #[cfg(not(target_os = "macos"))]
 const FUNCTION_MODIFIER_KEY: glfw::Modifiers = glfw::Modifiers::Control;
 #[cfg(target_os = "macos")]
 const FUNCTION_MODIFIER_KEY: glfw::Modifiers = glfw::Modifiers::Super;
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[derive(Eq, PartialEq, Debug)]
 pub enum CreateError {
     WindowAlreadyCreated,
     WindowCreationFailed,
     MonitorNotFound,
+    Engine(flutter_engine::CreateError),
 }
 
 impl std::fmt::Display for CreateError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let msg = match *self {
+        let msg = match self {
             CreateError::WindowCreationFailed => "Failed to create a window",
             CreateError::WindowAlreadyCreated => "Window was already created",
             CreateError::MonitorNotFound => "No monitor with the specified index found",
+            CreateError::Engine(err) => return write!(f, "Failed to create engine: {}", err),
         };
         f.write_str(msg)
     }
 }
 
+impl From<flutter_engine::CreateError> for CreateError {
+    fn from(err: flutter_engine::CreateError) -> Self {
+        CreateError::Engine(err)
+    }
+}
+
 pub enum WindowMode {
     Fullscreen(usize),
     Windowed,
@@ -91,17 +106,29 @@ pub struct FlutterWindow {
     glfw: glfw::Glfw,
     window: Arc<Mutex<glfw::Window>>,
     window_receiver: Receiver<(f64, glfw::WindowEvent)>,
-    _resource_window: glfw::Window,
+    resource_window: Mutex<glfw::Window>,
     _resource_window_receiver: Receiver<(f64, glfw::WindowEvent)>,
-    engine: FlutterEngine,
+    engine: RwLock<FlutterEngine>,
     pointer_currently_added: AtomicBool,
+    pending_pointer_events: Mutex<Vec<PointerEvent>>,
     window_pixels_per_screen_coordinate: AtomicU64,
     main_thread_receiver: Receiver<MainTheadFn>,
     main_thread_sender: Sender<MainTheadFn>,
     mouse_tracker: Mutex<HashMap<glfw::MouseButton, glfw::Action>>,
     window_handler: Arc<Mutex<GlfwWindowHandler>>,
     platform_task_handler: Arc<GlfwPlatformTaskHandler>,
+    vsync_handler: Arc<GlfwVsyncHandler>,
     plugins: RwLock<PluginRegistrar>,
+    #[cfg(target_os = "linux")]
+    settings_watcher: Mutex<Option<SettingsWatcher>>,
+    locales: Mutex<Vec<Locale>>,
+}
+
+impl Drop for FlutterWindow {
+    fn drop(&mut self) {
+        // engines dropped on other threads are only shut down while the loop runs
+        FlutterEngine::shutdown_retired_engines();
+    }
 }
 
 impl FlutterWindow {
@@ -110,6 +137,7 @@ impl FlutterWindow {
         window_args: &WindowArgs,
         assets_path: PathBuf,
         arguments: Vec<String>,
+        aot_data: Option<AotData>,
     ) -> Result<Self, CreateError> {
         glfw.window_hint(glfw::WindowHint::ContextVersion(3, 2));
         glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
@@ -162,22 +190,23 @@ impl FlutterWindow {
             .ok_or(CreateError::WindowCreationFailed)?;
         glfw.default_window_hints();
 
-        let render_ctx = window.render_context();
-
-        // Wrap
-        let window = Arc::new(Mutex::new(window));
+        let refresh_rate = monitor_refresh_rate(glfw, &window).unwrap_or(DEFAULT_REFRESH_RATE);
 
         // Create engine
         let platform_task_handler = Arc::new(GlfwPlatformTaskHandler::new());
-        let opengl_handler = GlfwOpenGLHandler::new(render_ctx, res_window.render_context());
+        let vsync_handler = Arc::new(GlfwVsyncHandler::new(refresh_rate));
+        let engine = build_engine(
+            &mut window,
+            &mut res_window,
+            platform_task_handler.clone(),
+            vsync_handler.clone(),
+            assets_path,
+            arguments,
+            aot_data,
+        )?;
 
-        let engine = FlutterEngineBuilder::new()
-            .with_platform_handler(platform_task_handler.clone())
-            .with_opengl(opengl_handler)
-            .with_asset_path(assets_path)
-            .with_args(arguments)
-            .build()
-            .expect("Failed to create engine");
+        // Wrap
+        let window = Arc::new(Mutex::new(window));
 
         // Main thread callbacks
         let (main_tx, main_rx) = mpsc::channel();
@@ -207,22 +236,28 @@ impl FlutterWindow {
             glfw: glfw.clone(),
             window,
             window_receiver: receiver,
-            _resource_window: res_window,
+            resource_window: Mutex::new(res_window),
             _resource_window_receiver: res_window_recv,
-            engine,
+            engine: RwLock::new(engine),
             pointer_currently_added: AtomicBool::new(false),
+            pending_pointer_events: Mutex::new(Vec::new()),
             window_pixels_per_screen_coordinate: AtomicU64::new(0.0_f64.to_bits()),
             main_thread_receiver: main_rx,
             main_thread_sender: main_tx,
             mouse_tracker: Mutex::new(Default::default()),
             window_handler,
             platform_task_handler,
+            vsync_handler,
             plugins: RwLock::new(plugins),
+            #[cfg(target_os = "linux")]
+            settings_watcher: Mutex::new(None),
+            locales: Mutex::new(Vec::new()),
         })
     }
 
+    /// The currently running engine, which changes when the engine is restarted.
     pub fn engine(&self) -> FlutterEngine {
-        self.engine.clone()
+        self.engine.read().clone()
     }
 
     pub fn window(&self) -> Arc<Mutex<glfw::Window>> {
@@ -230,14 +265,14 @@ impl FlutterWindow {
     }
 
     pub fn create_texture(&self) -> Texture {
-        self.engine.create_texture()
+        self.engine().create_texture()
     }
 
     pub fn add_plugin<P>(&self, plugin: P) -> &Self
     where
         P: Plugin + 'static,
     {
-        self.plugins.write().add_plugin(&self.engine, plugin);
+        self.plugins.write().add_plugin(&self.engine(), plugin);
         self
     }
 
@@ -261,27 +296,27 @@ impl FlutterWindow {
     where
         C: Channel + 'static,
     {
-        self.engine.register_channel(channel)
+        self.engine().register_channel(channel)
     }
 
     pub fn remove_channel(&self, channel_name: &str) -> Option<Arc<dyn Channel>> {
-        self.engine.remove_channel(channel_name)
+        self.engine().remove_channel(channel_name)
     }
 
     pub fn with_channel<F>(&self, channel_name: &str, f: F)
     where
         F: FnMut(&dyn Channel),
     {
-        self.engine.with_channel(channel_name, f)
+        self.engine().with_channel(channel_name, f)
     }
 
     pub fn run(
         &self,
         mut custom_handler: Option<&mut WindowEventHandler>,
         mut frame_callback: Option<&mut PerFrameCallback>,
-    ) -> Result<(), ()> {
+    ) -> Result<(), EngineError> {
         // Start engine
-        self.engine.run()?;
+        self.engine().run()?;
 
         // send initial size callback to engine
         self.send_scale_or_size_change();
@@ -299,18 +334,20 @@ impl FlutterWindow {
             window.set_size_polling(true);
             window.set_content_scale_polling(true);
             window.set_refresh_polling(true);
+            window.set_pos_polling(true);
+            // the system locales are checked again on focus
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            window.set_focus_polling(true);
         }
 
-        self.with_plugin(
-            |localization: &flutter_plugins::localization::LocalizationPlugin| {
-                localization.send_locale(locale_config::Locale::current());
-            },
-        );
+        self.send_locales();
+        #[cfg(target_os = "linux")]
+        self.watch_desktop_settings();
 
         let mut glfw = self.glfw.clone();
         while !self.window.lock().should_close() {
             // Execute tasks and callbacks
-            let next_task_time = self.engine.execute_platform_tasks();
+            let next_task_time = self.engine().execute_platform_tasks()?;
 
             let callbacks: Vec<MainTheadFn> = self.main_thread_receiver.try_iter().collect();
             for mut cb in callbacks {
@@ -334,7 +371,7 @@ impl FlutterWindow {
             // Fetch events
             let events: Vec<(f64, glfw::WindowEvent)> =
                 glfw::flush_messages(&self.window_receiver).collect();
-            for (_, event) in events {
+            for (time, event) in events {
                 let run_default_handler = if let Some(custom_handler) = &mut custom_handler {
                     custom_handler(&self, event.clone())
                 } else if let glfw::WindowEvent::CursorPos(x, y) = event {
@@ -343,9 +380,11 @@ impl FlutterWindow {
                     true
                 };
                 if run_default_handler {
-                    self.handle_glfw_event(event);
+                    self.handle_event(time, event);
                 }
             }
+            // pointer events are batched, so coalesced mouse moves cost a single call
+            self.flush_pointer_events();
 
             if let Some(callback) = &mut frame_callback {
                 callback(&self);
@@ -364,8 +403,118 @@ impl FlutterWindow {
         Ok(())
     }
 
-    pub fn shutdown(self) {
-        self.engine.shutdown();
+    pub fn shutdown(self) -> Result<(), EngineError> {
+        self.engine().shutdown()
+    }
+
+    /// Start a new engine with the given assets and arguments in this window and shut down the
+    /// running one. All plugins are registered with the new engine, channels registered through
+    /// `register_channel` have to be registered again. If the new engine can't be started, the
+    /// running engine is kept.
+    ///
+    /// Has to be called on the platform thread while the window is running, e.g. from a callback
+    /// passed to `post_main_thread_callback`.
+    pub fn restart_engine(
+        &self,
+        assets_path: PathBuf,
+        arguments: Vec<String>,
+        aot_data: Option<AotData>,
+    ) -> Result<(), flutter_engine::CreateError> {
+        let old_engine = self.engine();
+        if !old_engine.is_platform_thread() {
+            return Err(flutter_engine::CreateError::Engine(
+                EngineError::NotOnPlatformThread,
+            ));
+        }
+        info!("Restarting engine with assets {:?}", assets_path);
+        let engine = build_engine(
+            &mut self.window.lock(),
+            &mut self.resource_window.lock(),
+            self.platform_task_handler.clone(),
+            self.vsync_handler.clone(),
+            assets_path,
+            arguments,
+            aot_data,
+        )?;
+        self.plugins.write().reinit(&engine);
+        if let Err(err) = engine.run() {
+            self.plugins.write().reinit(&old_engine);
+            return Err(flutter_engine::CreateError::Engine(err));
+        }
+        *self.engine.write() = engine;
+        if let Err(err) = old_engine.shutdown() {
+            error!("Failed to shut down previous engine: {}", err);
+        }
+
+        // the new engine starts without any state, so everything is sent again
+        self.pointer_currently_added.store(false, Ordering::Relaxed);
+        self.pending_pointer_events.lock().clear();
+        self.send_scale_or_size_change();
+        self.locales.lock().clear();
+        self.send_locales();
+        #[cfg(target_os = "linux")]
+        self.watch_desktop_settings();
+        Ok(())
+    }
+
+    /// Send the preferred locales of the user if they changed since they were last sent.
+    /// On macOS and Windows they are read from the system settings and checked again whenever the
+    /// window gains focus. Elsewhere they are taken from the environment of the process, which
+    /// doesn't change while it runs.
+    pub fn send_locales(&self) {
+        let locales = localization::system_locales();
+        let mut sent = self.locales.lock();
+        if *sent == locales {
+            return;
+        }
+        debug!("Sending locales {:?}", locales);
+        if let Err(err) = self.engine().update_locales(&locales).wait() {
+            error!("Failed to send locales: {}", err);
+        }
+        *sent = locales;
+    }
+
+    /// Send the preferences of the desktop and keep them in sync while running.
+    #[cfg(target_os = "linux")]
+    fn watch_desktop_settings(&self) {
+        // the sender is only used to post the changes back to the platform thread
+        let sender = Mutex::new(self.main_thread_sender.clone());
+        let task_handler = self.platform_task_handler.clone();
+        let watcher = SettingsWatcher::new(move |settings| {
+            let callback: MainTheadFn =
+                Box::new(move |window| window.send_desktop_settings(settings));
+            if sender.lock().send(callback).is_ok() {
+                task_handler.wake();
+            }
+        });
+        self.send_desktop_settings(watcher.settings());
+        *self.settings_watcher.lock() = Some(watcher);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_desktop_settings(&self, settings: DesktopSettings) {
+        if let Err(err) = self
+            .engine()
+            .update_accessibility_features(settings.accessibility_features)
+            .wait()
+        {
+            error!("Failed to send accessibility features: {}", err);
+        }
+        self.with_plugin(|plugin: &SettingsPlugin| {
+            use flutter_plugins::settings::PlatformBrightness;
+
+            let brightness = if settings.dark_theme {
+                PlatformBrightness::Dark
+            } else {
+                PlatformBrightness::Light
+            };
+            plugin
+                .start_message()
+                .set_text_scale_factor(settings.text_scale_factor)
+                .set_use_24_hour_format(settings.use_24_hour_format)
+                .set_platform_brightness(brightness)
+                .send();
+        });
     }
 
     fn send_scale_or_size_change(&self) {
@@ -381,15 +530,38 @@ impl FlutterWindow {
             "Setting framebuffer size to {:?}, scale to {}",
             framebuffer_size, scale.0
         );
-        self.engine.send_window_metrics_event(
-            framebuffer_size.0 as _,
-            framebuffer_size.1 as _,
-            f64::from(scale.0),
-        );
+        if let Err(err) = self
+            .engine()
+            .send_window_metrics_event(
+                framebuffer_size.0 as _,
+                framebuffer_size.1 as _,
+                f64::from(scale.0),
+            )
+            .wait()
+        {
+            error!("Failed to send window metrics: {}", err);
+        }
+    }
+
+    /// Convert the time glfw received an event at to the clock of the engine, in microseconds.
+    fn engine_timestamp(&self, time: f64) -> u64 {
+        let age = (self.glfw.get_time() - time).max(0.0);
+        FlutterEngine::current_time().saturating_sub((age * 1e9) as u64) / 1000
     }
 
-    fn send_pointer_event(
+    fn flush_pointer_events(&self) {
+        let events = mem::take(&mut *self.pending_pointer_events.lock());
+        if events.is_empty() {
+            return;
+        }
+        if let Err(err) = self.engine().send_pointer_events(&events).wait() {
+            error!("Failed to send pointer events: {}", err);
+        }
+    }
+
+    fn queue_pointer_event(
         &self,
+        timestamp: u64,
         phase: FlutterPointerPhase,
         (x, y): (f64, f64),
         signal_kind: FlutterPointerSignalKind,
@@ -400,7 +572,8 @@ impl FlutterWindow {
             && phase != FlutterPointerPhase::Add
             && phase != FlutterPointerPhase::Remove
         {
-            self.send_pointer_event(
+            self.queue_pointer_event(
+                timestamp,
                 FlutterPointerPhase::Add,
                 (x, y),
                 FlutterPointerSignalKind::None,
@@ -419,21 +592,18 @@ impl FlutterWindow {
             self.window_pixels_per_screen_coordinate
                 .load(Ordering::Relaxed),
         );
-        self.engine.send_pointer_event(
-            0,
+        self.pending_pointer_events.lock().push(PointerEvent {
+            device: 0,
             phase,
-            (
-                x * window_pixels_per_screen_coordinate,
-                y * window_pixels_per_screen_coordinate,
-            ),
+            x: x * window_pixels_per_screen_coordinate,
+            y: y * window_pixels_per_screen_coordinate,
             signal_kind,
-            (
-                scroll_delta_x * window_pixels_per_screen_coordinate,
-                scroll_delta_y * window_pixels_per_screen_coordinate,
-            ),
-            FlutterPointerDeviceKind::Mouse,
+            scroll_delta_x: scroll_delta_x * window_pixels_per_screen_coordinate,
+            scroll_delta_y: scroll_delta_y * window_pixels_per_screen_coordinate,
+            device_kind: FlutterPointerDeviceKind::Mouse,
             buttons,
-        );
+            timestamp,
+        });
 
         match phase {
             FlutterPointerPhase::Add => self.pointer_currently_added.store(true, Ordering::Relaxed),
@@ -445,6 +615,14 @@ impl FlutterWindow {
     }
 
     pub fn handle_glfw_event(&self, event: glfw::WindowEvent) {
+        self.handle_event(self.glfw.get_time(), event);
+        self.flush_pointer_events();
+    }
+
+    /// Handle an event glfw received at `time`. Pointer events are queued until the next call
+    /// to `flush_pointer_events`.
+    fn handle_event(&self, time: f64, event: glfw::WindowEvent) {
+        let timestamp = self.engine_timestamp(time);
         match event {
             glfw::WindowEvent::Refresh => {
                 let window = self.window.lock();
@@ -464,15 +642,22 @@ impl FlutterWindow {
                     scale.0
                 );
 
-                self.engine.send_window_metrics_event(
-                    framebuffer_size.0 as _,
-                    framebuffer_size.1 as _,
-                    f64::from(scale.0),
-                );
+                if let Err(err) = self
+                    .engine()
+                    .send_window_metrics_event(
+                        framebuffer_size.0 as _,
+                        framebuffer_size.1 as _,
+                        f64::from(scale.0),
+                    )
+                    .wait()
+                {
+                    error!("Failed to send window metrics: {}", err);
+                }
             }
             glfw::WindowEvent::CursorEnter(entered) => {
                 let cursor_pos = self.window.lock().get_cursor_pos();
-                self.send_pointer_event(
+                self.queue_pointer_event(
+                    timestamp,
                     if entered {
                         FlutterPointerPhase::Add
                     } else {
@@ -500,7 +685,8 @@ impl FlutterWindow {
                 } else {
                     FlutterPointerPhase::Hover
                 };
-                self.send_pointer_event(
+                self.queue_pointer_event(
+                    timestamp,
                     phase,
                     (x, y),
                     FlutterPointerSignalKind::None,
@@ -548,7 +734,8 @@ impl FlutterWindow {
                     glfw::MouseButton::Button5 => FlutterPointerMouseButtons::Forward,
                     _ => FlutterPointerMouseButtons::Primary,
                 };
-                self.send_pointer_event(
+                self.queue_pointer_event(
+                    timestamp,
                     phase,
                     (x, y),
                     FlutterPointerSignalKind::None,
@@ -569,7 +756,8 @@ impl FlutterWindow {
                 } else {
                     FlutterPointerPhase::Hover
                 };
-                self.send_pointer_event(
+                self.queue_pointer_event(
+                    timestamp,
                     phase,
                     (x, y),
                     FlutterPointerSignalKind::Scroll,
@@ -586,6 +774,14 @@ impl FlutterWindow {
             glfw::WindowEvent::ContentScale(_, _) => {
                 self.send_scale_or_size_change();
             }
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            glfw::WindowEvent::Focus(true) => self.send_locales(),
+            glfw::WindowEvent::Pos(_, _) => {
+                let mut glfw = self.glfw.clone();
+                if let Some(refresh_rate) = monitor_refresh_rate(&mut glfw, &self.window.lock()) {
+                    self.vsync_handler.set_refresh_rate(refresh_rate);
+                }
+            }
             glfw::WindowEvent::Char(char) => self.with_plugin_mut(
                 |text_input: &mut flutter_plugins::textinput::TextInputPlugin| {
                     text_input.with_state(|state| {
@@ -762,3 +958,48 @@ impl FlutterWindow {
         }
     }
 }
+
+fn build_engine(
+    window: &mut glfw::Window,
+    resource_window: &mut glfw::Window,
+    platform_task_handler: Arc<GlfwPlatformTaskHandler>,
+    vsync_handler: Arc<GlfwVsyncHandler>,
+    assets_path: PathBuf,
+    arguments: Vec<String>,
+    aot_data: Option<AotData>,
+) -> Result<FlutterEngine, flutter_engine::CreateError> {
+    let opengl_handler =
+        GlfwOpenGLHandler::new(window.render_context(), resource_window.render_context());
+    let mut builder = FlutterEngineBuilder::new()
+        .with_platform_handler(platform_task_handler)
+        .with_opengl(opengl_handler)
+        .with_vsync_handler(vsync_handler)
+        .with_asset_path(assets_path)
+        .with_args(arguments);
+    if let Some(aot_data) = aot_data {
+        builder = builder.with_aot_data(aot_data);
+    }
+    builder.build()
+}
+
+/// Refresh rate of the monitor the center of the window is on.
+fn monitor_refresh_rate(glfw: &mut glfw::Glfw, window: &glfw::Window) -> Option<u32> {
+    let (x, y) = window.get_pos();
+    let (width, height) = window.get_size();
+    let (center_x, center_y) = (x + width / 2, y + height / 2);
+    glfw.with_connected_monitors(|_, monitors| {
+        monitors.iter().find_map(|monitor| {
+            let (monitor_x, monitor_y) = monitor.get_pos();
+            let mode = monitor.get_video_mode()?;
+            if center_x >= monitor_x
+                && center_x < monitor_x + mode.width as i32
+                && center_y >= monitor_y
+                && center_y < monitor_y + mode.height as i32
+            {
+                Some(mode.refresh_rate)
+            } else {
+                None
+            }
+        })
+    })
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "accessibility")]
+
+use flutter_engine::codec::Value;
+use flutter_engine::semantics::{
+    Rect, SemanticsActions, SemanticsFlags, SemanticsNode, SemanticsTree, TextDirection,
+};
+use flutter_glfw::accessibility::{AccessibilityBridge, SemanticsProvider};
+use parking_lot::{Mutex, RwLock};
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+use zbus::{Connection, Message};
+
+const ACCESSIBLE: &str = "org.a11y.atspi.Accessible";
+const ACTION: &str = "org.a11y.atspi.Action";
+
+type ObjectRef = (String, OwnedObjectPath);
+
+/// A private session bus, killed when dropped.
+struct DBusDaemon {
+    child: Child,
+    address: String,
+}
+
+impl DBusDaemon {
+    fn start() -> Option<Self> {
+        let mut child = match Command::new("dbus-daemon")
+            .arg("--session")
+            .arg("--nofork")
+            .arg("--print-address")
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("Skipping test, failed to start dbus-daemon: {}", err);
+                return None;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(Self {
+            child,
+            address: address.trim().to_owned(),
+        })
+    }
+}
+
+impl Drop for DBusDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Default)]
+struct TestProvider {
+    tree: RwLock<SemanticsTree>,
+    dispatched: Mutex<Vec<(i32, SemanticsActions)>>,
+}
+
+impl SemanticsProvider for TestProvider {
+    fn with_tree(&self, f: &mut dyn FnMut(&SemanticsTree)) {
+        f(&self.tree.read());
+    }
+
+    fn dispatch_action(&self, node_id: i32, action: SemanticsActions, _args: Option<Value>) {
+        self.dispatched.lock().push((node_id, action));
+    }
+}
+
+fn node(id: i32, label: &str, flags: SemanticsFlags, children: Vec<i32>) -> SemanticsNode {
+    SemanticsNode {
+        id,
+        flags,
+        actions: if flags.contains(SemanticsFlags::IS_BUTTON) {
+            SemanticsActions::TAP
+        } else {
+            SemanticsActions::empty()
+        },
+        text_selection_base: -1,
+        text_selection_extent: -1,
+        scroll_child_count: 0,
+        scroll_index: 0,
+        scroll_position: 0.0,
+        scroll_extent_max: 0.0,
+        scroll_extent_min: 0.0,
+        elevation: 0.0,
+        thickness: 0.0,
+        label: label.to_owned(),
+        hint: String::new(),
+        value: String::new(),
+        increased_value: String::new(),
+        decreased_value: String::new(),
+        text_direction: TextDirection::Ltr,
+        rect: Rect {
+            left: 0.0,
+            top: 0.0,
+            right: 100.0,
+            bottom: 100.0,
+        },
+        transform: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        children_in_hit_test_order: children.clone(),
+        children_in_traversal_order: children,
+        custom_actions: vec![],
+        platform_view_id: -1,
+    }
+}
+
+fn call(connection: &Connection, dest: &str, path: &str, iface: &str, method: &str) -> Message {
+    connection
+        .call_method(Some(dest), path, Some(iface), method, &())
+        .unwrap()
+}
+
+fn get_property(connection: &Connection, dest: &str, path: &str, name: &str) -> OwnedValue {
+    connection
+        .call_method(
+            Some(dest),
+            path,
+            Some("org.freedesktop.DBus.Properties"),
+            "Get",
+            &(ACCESSIBLE, name),
+        )
+        .unwrap()
+        .body()
+        .unwrap()
+}
+
+#[test]
+fn exports_tree_over_atspi() {
+    let daemon = match DBusDaemon::start() {
+        Some(daemon) => daemon,
+        None => return,
+    };
+
+    let provider = Arc::new(TestProvider::default());
+    provider.tree.write().update(vec![
+        node(0, "", SemanticsFlags::empty(), vec![1]),
+        node(
+            1,
+            "Increment",
+            SemanticsFlags::IS_BUTTON | SemanticsFlags::HAS_ENABLED_STATE,
+            vec![],
+        ),
+    ]);
+
+    let bridge =
+        AccessibilityBridge::start_on_bus(&daemon.address, provider.clone(), "test").unwrap();
+    let bus_name = bridge.bus_name().to_owned();
+    let client = Connection::new_for_address(&daemon.address, true).unwrap();
+    // give the bridge a chance to pick up the tree
+    thread::sleep(Duration::from_millis(300));
+
+    let root = "/org/a11y/atspi/accessible/root";
+    let name = get_property(&client, &bus_name, root, "Name");
+    assert_eq!(String::try_from(name).unwrap(), "test");
+    let children: Vec<ObjectRef> = call(&client, &bus_name, root, ACCESSIBLE, "GetChildren")
+        .body()
+        .unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].1.as_str(), "/org/a11y/atspi/accessible/0");
+
+    let children: Vec<ObjectRef> = call(
+        &client,
+        &bus_name,
+        "/org/a11y/atspi/accessible/0",
+        ACCESSIBLE,
+        "GetChildren",
+    )
+    .body()
+    .unwrap();
+    let button = children[0].1.as_str().to_owned();
+    assert_eq!(button, "/org/a11y/atspi/accessible/1");
+
+    let role: u32 = call(&client, &bus_name, &button, ACCESSIBLE, "GetRole")
+        .body()
+        .unwrap();
+    assert_eq!(role, 43);
+    let name = get_property(&client, &bus_name, &button, "Name");
+    assert_eq!(String::try_from(name).unwrap(), "Increment");
+    // disabled, so neither enabled nor sensitive
+    let state: Vec<u32> = call(&client, &bus_name, &button, ACCESSIBLE, "GetState")
+        .body()
+        .unwrap();
+    assert_eq!(state[0] & (1 << 8 | 1 << 24), 0);
+
+    let actions: Vec<(String, String, String)> =
+        call(&client, &bus_name, &button, ACTION, "GetActions")
+            .body()
+            .unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].0, "click");
+    let done: bool = client
+        .call_method(Some(&bus_name), &button, Some(ACTION), "DoAction", &0i32)
+        .unwrap()
+        .body()
+        .unwrap();
+    assert!(done);
+    assert_eq!(
+        provider.dispatched.lock().as_slice(),
+        &[(1, SemanticsActions::TAP)]
+    );
+
+    // removed nodes are unregistered
+    provider
+        .tree
+        .write()
+        .update(vec![node(0, "", SemanticsFlags::empty(), vec![])]);
+    thread::sleep(Duration::from_millis(300));
+    assert!(client
+        .call_method(Some(&bus_name), &button, Some(ACCESSIBLE), "GetRole", &())
+        .is_err());
+}
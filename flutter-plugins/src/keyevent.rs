@@ -1,5 +1,7 @@
 use std::sync::Weak;
 
+use log::error;
+
 use serde::{Deserialize, Serialize};
 
 use flutter_engine::{
@@ -72,7 +74,9 @@ impl KeyEventPlugin {
 
     pub fn key_action(&self, action: KeyAction) {
         self.with_channel(|channel| {
-            channel.send(action);
+            if let Err(err) = channel.send(action).wait() {
+                error!("Failed to send key event: {}", err);
+            }
         });
     }
 }
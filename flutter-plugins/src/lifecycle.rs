@@ -1,7 +1,7 @@
 //! Plugin to work with locales.
 //! It handles flutter/localization type message.
 
-use log::debug;
+use log::{debug, error};
 use std::sync::Weak;
 
 use flutter_engine::{
@@ -44,21 +44,27 @@ impl LifecyclePlugin {
     pub fn send_app_is_inactive(&self) {
         debug!("Sending app is inactive");
         if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.inactive");
+            if let Err(err) = channel.send("AppLifecycleState.inactive").wait() {
+                error!("Failed to send lifecycle state: {}", err);
+            }
         }
     }
 
     pub fn send_app_is_resumed(&self) {
         debug!("Sending app is resumed");
         if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.resumed");
+            if let Err(err) = channel.send("AppLifecycleState.resumed").wait() {
+                error!("Failed to send lifecycle state: {}", err);
+            }
         }
     }
 
     pub fn send_app_is_paused(&self) {
         debug!("Sending app is paused");
         if let Some(channel) = self.channel.upgrade() {
-            channel.send("AppLifecycleState.paused");
+            if let Err(err) = channel.send("AppLifecycleState.paused").wait() {
+                error!("Failed to send lifecycle state: {}", err);
+            }
         }
     }
 }
@@ -2,9 +2,11 @@
 //! It handles flutter/localization type message.
 
 use log::{debug, error, info, warn};
+use std::env;
 use std::sync::Weak;
 
 use flutter_engine::channel::MethodCall;
+use flutter_engine::ffi::Locale;
 use flutter_engine::{
     channel::{MethodCallHandler, MethodChannel},
     codec::JSON_CODEC,
@@ -39,6 +41,8 @@ impl Plugin for LocalizationPlugin {
 }
 
 impl LocalizationPlugin {
+    /// Send a single locale over the legacy `setLocale` method.
+    /// Engines that support it should be sent the full list with `FlutterEngine::update_locales`.
     pub fn send_locale(&self, locale: locale_config::Locale) {
         debug!("Sending locales to flutter");
         if let Some(channel) = self.channel.upgrade() {
@@ -61,13 +65,107 @@ impl LocalizationPlugin {
                 }
             }
 
-            channel.invoke_method("setLocale", languages)
+            if let Err(err) = channel.invoke_method("setLocale", languages).wait() {
+                error!("Failed to send locale: {}", err);
+            }
         } else {
             error!("Failed to upgrade channel to send message");
         }
     }
 }
 
+/// The preferred locales of the user. On Windows and macOS they are read from the system settings,
+/// elsewhere or if those are empty they are taken from the environment, see `env_locales`.
+pub fn system_locales() -> Vec<Locale> {
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    {
+        let locales = convert_locale(&locale_config::Locale::user_default());
+        if !locales.is_empty() {
+            return locales;
+        }
+    }
+    env_locales()
+}
+
+/// Convert the language ranges of a `locale_config` locale which apply to all categories.
+pub fn convert_locale(locale: &locale_config::Locale) -> Vec<Locale> {
+    let mut locales = Vec::new();
+    for (tag, language) in locale.tags() {
+        if tag.is_some() {
+            continue;
+        }
+        let loc = match unic_locale::parser::parse_locale(language.as_ref()) {
+            Ok(loc) => loc,
+            Err(_) => {
+                warn!("Failed to parse language range: {}", language);
+                continue;
+            }
+        };
+        let mut converted = Locale::new(loc.get_language());
+        converted.country = loc.get_region().map(str::to_owned);
+        converted.script = loc.get_script().map(str::to_owned);
+        converted.variant = loc.get_variants().next().map(str::to_owned);
+        if !converted.language.is_empty() && !locales.contains(&converted) {
+            locales.push(converted);
+        }
+    }
+    locales
+}
+
+/// The preferred locales of the user, taken from `LANGUAGE` followed by the first set variable of
+/// `LC_ALL`, `LC_MESSAGES` and `LANG`, like gettext does.
+pub fn env_locales() -> Vec<Locale> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty());
+
+    let mut names = Vec::new();
+    // LANGUAGE is ignored by gettext when the locale is C
+    if let Some(locale) = &locale {
+        if parse_posix_locale(locale).is_some() {
+            if let Ok(language) = env::var("LANGUAGE") {
+                names.extend(language.split(':').map(str::to_owned));
+            }
+        }
+    }
+    names.extend(locale);
+
+    let mut locales = Vec::new();
+    for locale in names.iter().filter_map(|name| parse_posix_locale(name)) {
+        if !locales.contains(&locale) {
+            locales.push(locale);
+        }
+    }
+    locales
+}
+
+/// Parse a locale of the form `language[_territory][.codeset][@modifier]`.
+pub fn parse_posix_locale(name: &str) -> Option<Locale> {
+    let (name, modifier) = match name.find('@') {
+        Some(index) => (&name[..index], Some(&name[index + 1..])),
+        None => (name, None),
+    };
+    let name = name.split('.').next().unwrap_or_default();
+    let mut parts = name.splitn(2, '_');
+    let language = parts.next().filter(|language| !language.is_empty())?;
+    if language == "C" || language == "POSIX" {
+        return None;
+    }
+
+    let mut locale = Locale::new(language);
+    locale.country = parts.next().map(str::to_owned);
+    match modifier {
+        Some("latin") => locale.script = Some("Latn".to_owned()),
+        Some("cyrillic") => locale.script = Some("Cyrl".to_owned()),
+        Some("devanagari") => locale.script = Some("Deva".to_owned()),
+        // only selects the currency
+        Some("euro") | None => {}
+        Some(variant) => locale.variant = Some(variant.to_owned()),
+    }
+    Some(locale)
+}
+
 struct Handler;
 
 impl MethodCallHandler for Handler {
@@ -80,3 +178,40 @@ impl MethodCallHandler for Handler {
         call.not_implemented()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_posix_locale() {
+        assert_eq!(parse_posix_locale("de"), Some(Locale::new("de")));
+        assert_eq!(parse_posix_locale("C.UTF-8"), None);
+        assert_eq!(parse_posix_locale("POSIX"), None);
+        assert_eq!(parse_posix_locale(""), None);
+
+        let locale = parse_posix_locale("sr_RS.UTF-8@latin").unwrap();
+        assert_eq!(locale.language, "sr");
+        assert_eq!(locale.country.as_deref(), Some("RS"));
+        assert_eq!(locale.script.as_deref(), Some("Latn"));
+        assert_eq!(locale.variant, None);
+
+        let locale = parse_posix_locale("ca_ES@valencia").unwrap();
+        assert_eq!(locale.country.as_deref(), Some("ES"));
+        assert_eq!(locale.variant.as_deref(), Some("valencia"));
+
+        let locale = parse_posix_locale("de_DE@euro").unwrap();
+        assert_eq!(locale.variant, None);
+    }
+
+    #[test]
+    fn test_convert_locale() {
+        let locale = locale_config::Locale::new("sr-Latn-RS,en").unwrap();
+        let locales = convert_locale(&locale);
+        assert_eq!(locales.len(), 2);
+        assert_eq!(locales[0].language, "sr");
+        assert_eq!(locales[0].country.as_deref(), Some("RS"));
+        assert_eq!(locales[0].script.as_deref(), Some("Latn"));
+        assert_eq!(locales[1], Locale::new("en"));
+    }
+}
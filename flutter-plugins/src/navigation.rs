@@ -1,7 +1,7 @@
 //! This plugin is used for navigation in an app.
 //! It handles flutter/navigation type messages.
 
-use log::debug;
+use log::{debug, error};
 use std::sync::Weak;
 
 use flutter_engine::channel::MethodCall;
@@ -51,16 +51,29 @@ impl NavigationPlugin {
 
     pub fn set_initial_route(&self, initial_route: &str) {
         self.with_channel(|channel| {
-            channel.invoke_method("setInitialRoute", initial_route.to_string())
+            if let Err(err) = channel
+                .invoke_method("setInitialRoute", initial_route.to_string())
+                .wait()
+            {
+                error!("Failed to send navigation: {}", err);
+            }
         });
     }
 
     pub fn push_route(&self, route: &str) {
-        self.with_channel(|channel| channel.invoke_method("pushRoute", route.to_string()));
+        self.with_channel(|channel| {
+            if let Err(err) = channel.invoke_method("pushRoute", route.to_string()).wait() {
+                error!("Failed to send navigation: {}", err);
+            }
+        });
     }
 
     pub fn pop_route(&self) {
-        self.with_channel(|channel| channel.invoke_method("popRoute", Value::Null));
+        self.with_channel(|channel| {
+            if let Err(err) = channel.invoke_method("popRoute", Value::Null).wait() {
+                error!("Failed to send navigation: {}", err);
+            }
+        });
     }
 }
 
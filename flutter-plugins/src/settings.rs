@@ -3,7 +3,7 @@
 
 use std::collections::HashMap;
 
-use log::debug;
+use log::{debug, error};
 use std::sync::Weak;
 
 use serde::{Deserialize, Serialize};
@@ -81,7 +81,9 @@ impl SettingsMessage<'_> {
     pub fn send(self) {
         if let Some(channel) = self.plugin.channel.upgrade() {
             debug!("Sending settings: {:?}", self.settings);
-            channel.send(self.settings);
+            if let Err(err) = channel.send(self.settings).wait() {
+                error!("Failed to send settings: {}", err);
+            }
         }
     }
 }
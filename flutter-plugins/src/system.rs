@@ -45,9 +45,12 @@ impl SystemPlugin {
     pub fn send_memory_pressure_warning(&self) {
         info!("Sending memory pressure warning");
         if let Some(channel) = self.channel.upgrade() {
-            channel.send(SystemMsg {
+            let message = SystemMsg {
                 r#type: "memoryPressure".to_string(),
-            });
+            };
+            if let Err(err) = channel.send(message).wait() {
+                error!("Failed to send memory pressure warning: {}", err);
+            }
         } else {
             error!("Failed to upgrade channel to send memory pressure warning");
         }
@@ -1,7 +1,7 @@
 //! This plugin is used by TextField to edit text and control caret movement.
 //! It handles flutter/textinput type message.
 
-use log::debug;
+use log::{debug, error};
 use std::sync::{Arc, RwLock, Weak};
 
 use serde::{Deserialize, Serialize};
@@ -100,7 +100,12 @@ impl TextInputPlugin {
             let mut args: Vec<Value> = Vec::new();
             args.push_as_value(data.client_id);
             args.push_as_value("TextInputAction.".to_owned() + action);
-            channel.invoke_method("TextInputClient.performAction", args)
+            if let Err(err) = channel
+                .invoke_method("TextInputClient.performAction", args)
+                .wait()
+            {
+                error!("Failed to send text input action: {}", err);
+            }
         });
     }
 
@@ -112,7 +117,12 @@ impl TextInputPlugin {
                 let mut args: Vec<Value> = Vec::new();
                 args.push_as_value(client_id);
                 args.push_as_value(state);
-                channel.invoke_method("TextInputClient.updateEditingState", args)
+                if let Err(err) = channel
+                    .invoke_method("TextInputClient.updateEditingState", args)
+                    .wait()
+                {
+                    error!("Failed to send editing state: {}", err);
+                }
             }
         };
     }
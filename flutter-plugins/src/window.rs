@@ -5,18 +5,18 @@ use std::sync::{Arc, Weak};
 use serde::{Deserialize, Serialize};
 
 use flutter_engine::{
-    channel::{MethodCallHandler, MethodChannel},
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
     codec::JSON_CODEC,
+    method_channel,
     plugins::Plugin,
     FlutterEngine,
 };
 
-use flutter_engine::channel::MethodCall;
 use parking_lot::Mutex;
 
 const PLUGIN_NAME: &str = module_path!();
-const CHANNEL_NAME: &str = "flutter-rs/window";
 
+#[method_channel(name = "flutter-rs/window", codec = "JSON_CODEC")]
 pub trait WindowHandler {
     fn close(&mut self);
 
@@ -36,12 +36,16 @@ pub trait WindowHandler {
 
     fn is_visible(&mut self) -> bool;
 
+    #[method(name = "set_pos")]
     fn set_pos(&mut self, pos: PositionParams);
 
+    #[method(name = "get_pos")]
     fn get_pos(&mut self) -> PositionParams;
 
+    #[method(name = "start_drag")]
     fn start_drag(&mut self);
 
+    #[method(name = "end_drag")]
     fn end_drag(&mut self);
 }
 
@@ -66,11 +70,11 @@ impl Plugin for WindowPlugin {
 
     fn init(&mut self, engine: &FlutterEngine) {
         self.channel = engine.register_channel(MethodChannel::new(
-            CHANNEL_NAME,
+            WindowHandlerChannel::NAME,
             Handler {
                 handler: self.handler.clone(),
             },
-            &JSON_CODEC,
+            WindowHandlerChannel::codec(),
         ));
     }
 }
@@ -81,50 +85,7 @@ struct Handler {
 
 impl MethodCallHandler for Handler {
     fn on_method_call(&mut self, call: MethodCall) {
-        match call.method().as_str() {
-            "maximize" => {
-                self.handler.lock().maximize();
-                call.success_empty()
-            }
-            "iconify" => {
-                self.handler.lock().iconify();
-                call.success_empty()
-            }
-            "restore" => {
-                self.handler.lock().restore();
-                call.success_empty()
-            }
-            "isMaximized" => call.success(self.handler.lock().is_maximized()),
-            "isIconified" => call.success(self.handler.lock().is_iconified()),
-            "isVisible" => call.success(self.handler.lock().is_visible()),
-            "show" => {
-                self.handler.lock().show();
-                call.success_empty()
-            }
-            "hide" => {
-                self.handler.lock().hide();
-                call.success_empty()
-            }
-            "close" => {
-                self.handler.lock().close();
-                call.success_empty()
-            }
-            "set_pos" => {
-                let args: PositionParams = call.args();
-                self.handler.lock().set_pos(args);
-                call.success_empty()
-            }
-            "get_pos" => call.success(self.handler.lock().get_pos()),
-            "start_drag" => {
-                self.handler.lock().start_drag();
-                call.success_empty()
-            }
-            "end_drag" => {
-                self.handler.lock().end_drag();
-                call.success_empty()
-            }
-            _ => call.not_implemented(),
-        }
+        WindowHandlerChannel::dispatch(&mut *self.handler.lock(), call)
     }
 }
 
@@ -0,0 +1,53 @@
+//! Generate Dart and Rust bindings of the channels declared in a schema, see
+//! `flutter_tools::codegen` for its format.
+
+use clap::{App, Arg, ArgGroup, ArgMatches};
+use flutter_tools::codegen::{self, Schema};
+
+fn main() {
+    let matches = App::new("flutter-codegen")
+        .about("Generates Dart and Rust bindings of flutter-rs channels")
+        .arg(
+            Arg::with_name("schema")
+                .required(true)
+                .help("The TOML schema declaring the channels"),
+        )
+        .arg(
+            Arg::with_name("dart")
+                .long("dart")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the Dart bindings to FILE"),
+        )
+        .arg(
+            Arg::with_name("rust")
+                .long("rust")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the Rust bindings to FILE"),
+        )
+        .group(
+            ArgGroup::with_name("output")
+                .arg("dart")
+                .arg("rust")
+                .multiple(true)
+                .required(true),
+        )
+        .get_matches();
+
+    if let Err(err) = run(&matches) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), codegen::Error> {
+    let schema = Schema::load(matches.value_of("schema").unwrap())?;
+    if let Some(path) = matches.value_of("dart") {
+        std::fs::write(path, codegen::dart::generate(&schema))?;
+    }
+    if let Some(path) = matches.value_of("rust") {
+        std::fs::write(path, codegen::rust::generate(&schema))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,284 @@
+//! Dart bindings: data classes, and per channel a client of the methods implemented in Rust or
+//! an abstract class to implement them in Dart, plus streams of its events.
+
+use super::{Channel, Class, CodeWriter, Codec, Field, Handler, Method, Schema, Type};
+
+pub fn generate(schema: &Schema) -> String {
+    let mut w = CodeWriter::new(2);
+    w.line("// Generated by flutter-codegen, do not edit.");
+    w.line("");
+    w.line("import 'package:flutter/services.dart';");
+    for class in &schema.classes {
+        w.line("");
+        write_class(&mut w, class);
+    }
+    for channel in &schema.channels {
+        w.line("");
+        match channel.handler {
+            Handler::Rust => write_client(&mut w, channel),
+            Handler::Dart => write_handler(&mut w, channel),
+        }
+    }
+    w.finish()
+}
+
+fn dart_type(ty: &Type) -> String {
+    match ty {
+        Type::Bool => "bool".to_owned(),
+        Type::Int => "int".to_owned(),
+        Type::Double => "double".to_owned(),
+        Type::String => "String".to_owned(),
+        Type::List(ty) => format!("List<{}>", dart_type(ty)),
+        Type::Map(ty) => format!("Map<String, {}>", dart_type(ty)),
+        Type::Class(name) => name.clone(),
+        Type::Nullable(ty) => format!("{}?", dart_type(ty)),
+    }
+}
+
+fn codec(codec: Codec) -> &'static str {
+    match codec {
+        Codec::Standard => "StandardMethodCodec()",
+        Codec::Json => "JSONMethodCodec()",
+    }
+}
+
+/// An expression converting `value`, an `Object?` received from the codec, to `ty`.
+fn decode(ty: &Type, value: &str) -> String {
+    match ty {
+        Type::Bool => format!("{} as bool", value),
+        Type::Int => format!("{} as int", value),
+        // the json codec decodes whole numbers as int
+        Type::Double => format!("({} as num).toDouble()", value),
+        Type::String => format!("{} as String", value),
+        Type::List(ty) => format!(
+            "({} as List<Object?>).map((e) => {}).toList()",
+            value,
+            decode(ty, "e")
+        ),
+        Type::Map(ty) => format!(
+            "({} as Map<Object?, Object?>).map((k, v) => MapEntry(k as String, {}))",
+            value,
+            decode(ty, "v")
+        ),
+        Type::Class(name) => format!("{}.decode({})", name, value),
+        Type::Nullable(ty) => format!("{0} == null ? null : {1}", value, decode(ty, value)),
+    }
+}
+
+/// An expression converting `value` of `ty` to an object the codec can encode.
+fn encode(ty: &Type, value: &str) -> String {
+    match ty {
+        Type::Nullable(ty) => encode_members(ty, value, "?."),
+        ty => encode_members(ty, value, "."),
+    }
+}
+
+fn encode_members(ty: &Type, value: &str, access: &str) -> String {
+    if ty.is_plain() {
+        return value.to_owned();
+    }
+    match ty {
+        Type::List(ty) => format!(
+            "{}{}map((e) => {}).toList()",
+            value,
+            access,
+            encode(ty, "e")
+        ),
+        Type::Map(ty) => format!(
+            "{}{}map((k, v) => MapEntry(k, {}))",
+            value,
+            access,
+            encode(ty, "v")
+        ),
+        Type::Class(_) => format!("{}{}encode()", value, access),
+        _ => value.to_owned(),
+    }
+}
+
+fn write_class(w: &mut CodeWriter, class: &Class) {
+    w.open(format!("class {} {{", class.name));
+    if class.fields.is_empty() {
+        w.line(format!("{}();", class.name));
+    } else {
+        w.open(format!("{}({{", class.name));
+        for field in &class.fields {
+            match field.ty {
+                Type::Nullable(_) => w.line(format!("this.{},", field.name)),
+                _ => w.line(format!("required this.{},", field.name)),
+            }
+        }
+        w.close("});");
+        w.line("");
+        for field in &class.fields {
+            w.line(format!("final {} {};", dart_type(&field.ty), field.name));
+        }
+    }
+    w.line("");
+
+    w.open("Object encode() {");
+    w.open("return <String, Object?>{");
+    for field in &class.fields {
+        w.line(format!(
+            "'{}': {},",
+            field.name,
+            encode(&field.ty, &field.name)
+        ));
+    }
+    w.close("};");
+    w.close("}");
+    w.line("");
+
+    w.open(format!("static {} decode(Object? message) {{", class.name));
+    if class.fields.is_empty() {
+        w.line(format!("return {}();", class.name));
+    } else {
+        w.line("final map = message as Map<Object?, Object?>;");
+        w.open(format!("return {}(", class.name));
+        for field in &class.fields {
+            let value = format!("map['{}']", field.name);
+            w.line(format!("{}: {},", field.name, decode(&field.ty, &value)));
+        }
+        w.close(");");
+    }
+    w.close("}");
+    w.close("}");
+}
+
+fn channel_field(w: &mut CodeWriter, channel: &Channel) {
+    w.line(format!(
+        "static const MethodChannel _channel = MethodChannel('{}', {});",
+        channel.channel,
+        codec(channel.codec)
+    ));
+}
+
+fn signature(method: &Method) -> String {
+    let returns = match &method.returns {
+        Some(ty) => dart_type(ty),
+        None => "void".to_owned(),
+    };
+    let args: Vec<String> = method
+        .args
+        .iter()
+        .map(|arg| format!("{} {}", dart_type(&arg.ty), arg.name))
+        .collect();
+    format!("Future<{}> {}({})", returns, method.name, args.join(", "))
+}
+
+/// The arguments of a call, a single argument is sent as it is, more are sent as a map.
+fn encode_args(args: &[Field]) -> Option<String> {
+    match args {
+        [] => None,
+        [arg] => Some(encode(&arg.ty, &arg.name)),
+        args => {
+            let entries: Vec<String> = args
+                .iter()
+                .map(|arg| format!("'{}': {}", arg.name, encode(&arg.ty, &arg.name)))
+                .collect();
+            Some(format!("<String, Object?>{{{}}}", entries.join(", ")))
+        }
+    }
+}
+
+fn decode_args(args: &[Field]) -> Vec<String> {
+    match args {
+        [arg] => vec![decode(&arg.ty, "call.arguments")],
+        args => args
+            .iter()
+            .map(|arg| decode(&arg.ty, &format!("args['{}']", arg.name)))
+            .collect(),
+    }
+}
+
+fn write_events(w: &mut CodeWriter, channel: &Channel) {
+    for event in &channel.events {
+        w.line("");
+        w.open(format!(
+            "static Stream<{}> get {} {{",
+            dart_type(&event.ty),
+            event.name
+        ));
+        w.line(format!(
+            "const channel = EventChannel('{}', {});",
+            channel.event_channel(event),
+            codec(channel.codec)
+        ));
+        w.line(format!(
+            "return channel.receiveBroadcastStream().map((event) => {});",
+            decode(&event.ty, "event")
+        ));
+        w.close("}");
+    }
+}
+
+/// A class invoking the methods implemented in Rust.
+fn write_client(w: &mut CodeWriter, channel: &Channel) {
+    w.open(format!("class {} {{", channel.name));
+    channel_field(w, channel);
+    for method in &channel.methods {
+        w.line("");
+        w.open(format!("{} async {{", signature(method)));
+        let invoke = match encode_args(&method.args) {
+            Some(args) => format!(
+                "await _channel.invokeMethod<Object?>('{}', {})",
+                method.name, args
+            ),
+            None => format!("await _channel.invokeMethod<Object?>('{}')", method.name),
+        };
+        match &method.returns {
+            Some(ty) => {
+                w.line(format!("final result = {};", invoke));
+                w.line(format!("return {};", decode(ty, "result")));
+            }
+            None => w.line(format!("{};", invoke)),
+        }
+        w.close("}");
+    }
+    write_events(w, channel);
+    w.close("}");
+}
+
+/// An abstract class whose implementation answers the calls of Rust once passed to `setup`.
+fn write_handler(w: &mut CodeWriter, channel: &Channel) {
+    w.open(format!("abstract class {} {{", channel.name));
+    channel_field(w, channel);
+    for method in &channel.methods {
+        w.line("");
+        w.line(format!("{};", signature(method)));
+    }
+    w.line("");
+
+    w.open(format!("static void setup({}? api) {{", channel.name));
+    w.open("if (api == null) {");
+    w.line("_channel.setMethodCallHandler(null);");
+    w.line("return;");
+    w.close("}");
+    w.open("_channel.setMethodCallHandler((MethodCall call) async {");
+    for method in &channel.methods {
+        w.open(format!("if (call.method == '{}') {{", method.name));
+        if method.args.len() > 1 {
+            w.line("final args = call.arguments as Map<Object?, Object?>;");
+        }
+        let call = format!(
+            "await api.{}({})",
+            method.name,
+            decode_args(&method.args).join(", ")
+        );
+        match &method.returns {
+            Some(ty) => {
+                w.line(format!("final result = {};", call));
+                w.line(format!("return {};", encode(ty, "result")));
+            }
+            None => {
+                w.line(format!("{};", call));
+                w.line("return null;");
+            }
+        }
+        w.close("}");
+    }
+    w.line("throw MissingPluginException();");
+    w.close("});");
+    w.close("}");
+    write_events(w, channel);
+    w.close("}");
+}
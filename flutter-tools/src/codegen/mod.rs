@@ -0,0 +1,536 @@
+//! Generate matching Dart and Rust bindings from a channel schema.
+//!
+//! A schema is a TOML file declaring data classes and channels:
+//!
+//! ```toml
+//! [[class]]
+//! name = "Position"
+//! fields = [
+//!     { name = "x", type = "double" },
+//!     { name = "y", type = "double?" },
+//! ]
+//!
+//! [[channel]]
+//! name = "WindowApi"
+//! channel = "flutter-rs/window"
+//! codec = "json"
+//!
+//! [[channel.method]]
+//! name = "setPosition"
+//! args = [{ name = "position", type = "Position" }]
+//!
+//! [[channel.method]]
+//! name = "isMaximized"
+//! returns = "bool"
+//!
+//! [[channel.event]]
+//! name = "moved"
+//! type = "Position"
+//! ```
+//!
+//! Types are written like in Dart: `bool`, `int`, `double`, `String`, `List<T>`,
+//! `Map<String, T>` and the declared classes, a trailing `?` makes them nullable.
+//!
+//! The methods of a channel are implemented in Rust and invoked by Dart, unless the channel sets
+//! `handler = "dart"`. Events are always sent by Rust, on the channel `<channel>/<event>` unless
+//! the event sets its own `channel`. The codec is `standard` (the default) or `json`.
+
+pub mod dart;
+pub mod rust;
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Schema(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => error.fmt(f),
+            Error::Toml(error) => error.fmt(f),
+            Error::Schema(message) => write!(f, "Invalid schema: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Toml(error)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(try_from = "String")]
+pub enum Type {
+    Bool,
+    Int,
+    Double,
+    String,
+    List(Box<Type>),
+    /// A map with `String` keys
+    Map(Box<Type>),
+    Class(String),
+    Nullable(Box<Type>),
+}
+
+impl Type {
+    /// Whether values of this type are sent as they are, without converting classes.
+    pub fn is_plain(&self) -> bool {
+        match self {
+            Type::Bool | Type::Int | Type::Double | Type::String => true,
+            Type::List(ty) | Type::Map(ty) | Type::Nullable(ty) => ty.is_plain(),
+            Type::Class(_) => false,
+        }
+    }
+
+    fn classes<'a>(&'a self, classes: &mut Vec<&'a str>) {
+        match self {
+            Type::Bool | Type::Int | Type::Double | Type::String => {}
+            Type::List(ty) | Type::Map(ty) | Type::Nullable(ty) => ty.classes(classes),
+            Type::Class(name) => classes.push(name),
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(inner) = s.strip_suffix('?') {
+            let ty: Type = inner.parse()?;
+            return match ty {
+                Type::Nullable(_) => Err(format!("type {} is nullable twice", s)),
+                ty => Ok(Type::Nullable(Box::new(ty))),
+            };
+        }
+
+        if let Some(start) = s.find('<') {
+            if !s.ends_with('>') {
+                return Err(format!("unterminated type arguments in {}", s));
+            }
+            let args = &s[start + 1..s.len() - 1];
+            return match s[..start].trim() {
+                "List" => Ok(Type::List(Box::new(args.parse()?))),
+                "Map" => match split_type_args(args) {
+                    Some((key, value)) if key.trim() == "String" => {
+                        Ok(Type::Map(Box::new(value.parse()?)))
+                    }
+                    _ => Err(format!("expected Map<String, T>, got {}", s)),
+                },
+                base => Err(format!("unknown generic type {}", base)),
+            };
+        }
+
+        if !is_identifier(s) {
+            return Err(format!("invalid type {}", s));
+        }
+        Ok(match s {
+            "bool" => Type::Bool,
+            "int" => Type::Int,
+            "double" => Type::Double,
+            "String" => Type::String,
+            name => Type::Class(name.to_owned()),
+        })
+    }
+}
+
+impl TryFrom<String> for Type {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+/// Split `K, V` at its top level comma.
+fn split_type_args(args: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => return Some((&args[..i], &args[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    #[default]
+    Standard,
+    Json,
+}
+
+/// The side implementing the methods of a channel.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Handler {
+    #[default]
+    Rust,
+    Dart,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Class {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Method {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<Field>,
+    #[serde(default)]
+    pub returns: Option<Type>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Event {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+    #[serde(default)]
+    pub channel: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Channel {
+    pub name: String,
+    pub channel: String,
+    #[serde(default)]
+    pub codec: Codec,
+    #[serde(default)]
+    pub handler: Handler,
+    #[serde(default, rename = "method")]
+    pub methods: Vec<Method>,
+    #[serde(default, rename = "event")]
+    pub events: Vec<Event>,
+}
+
+impl Channel {
+    pub fn event_channel(&self, event: &Event) -> String {
+        match &event.channel {
+            Some(channel) => channel.clone(),
+            None => format!("{}/{}", self.channel, event.name),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    #[serde(default, rename = "class")]
+    pub classes: Vec<Class>,
+    #[serde(default, rename = "channel")]
+    pub channels: Vec<Channel>,
+}
+
+impl Schema {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let schema: Schema = toml::from_str(source)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut classes = HashSet::new();
+        for class in &self.classes {
+            check_type_name(&class.name)?;
+            if !classes.insert(class.name.as_str()) {
+                return Err(schema_error(format!(
+                    "class {} is declared twice",
+                    class.name
+                )));
+            }
+        }
+        let check_type = |ty: &Type, context: &str| {
+            let mut used = Vec::new();
+            ty.classes(&mut used);
+            match used.into_iter().find(|name| !classes.contains(name)) {
+                Some(name) => Err(schema_error(format!(
+                    "unknown class {} in {}",
+                    name, context
+                ))),
+                None => Ok(()),
+            }
+        };
+
+        for class in &self.classes {
+            let mut fields = HashSet::new();
+            for field in &class.fields {
+                check_member_name(&field.name)?;
+                if !fields.insert(field.name.as_str()) {
+                    return Err(schema_error(format!(
+                        "field {}.{} is declared twice",
+                        class.name, field.name
+                    )));
+                }
+                check_type(&field.ty, &format!("{}.{}", class.name, field.name))?;
+            }
+        }
+
+        let mut channels = HashSet::new();
+        for channel in &self.channels {
+            check_type_name(&channel.name)?;
+            if !channels.insert(channel.name.as_str()) || classes.contains(channel.name.as_str()) {
+                return Err(schema_error(format!("{} is declared twice", channel.name)));
+            }
+
+            let mut members = HashSet::new();
+            for method in &channel.methods {
+                let context = format!("{}.{}", channel.name, method.name);
+                check_member_name(&method.name)?;
+                if !members.insert(method.name.as_str()) {
+                    return Err(schema_error(format!("{} is declared twice", context)));
+                }
+                let mut args = HashSet::new();
+                for arg in &method.args {
+                    check_member_name(&arg.name)?;
+                    // arguments of a call with several arguments are looked up by the camel case
+                    // version of their Rust name
+                    if camel_case(&snake_case(&arg.name)) != arg.name || is_rust_keyword(&arg.name)
+                    {
+                        return Err(schema_error(format!(
+                            "argument {} of {} has to be a lower camel case name",
+                            arg.name, context
+                        )));
+                    }
+                    if !args.insert(arg.name.as_str()) {
+                        return Err(schema_error(format!(
+                            "argument {} of {} is declared twice",
+                            arg.name, context
+                        )));
+                    }
+                    check_type(&arg.ty, &context)?;
+                }
+                if let Some(returns) = &method.returns {
+                    check_type(returns, &context)?;
+                }
+            }
+            for event in &channel.events {
+                let context = format!("{}.{}", channel.name, event.name);
+                check_member_name(&event.name)?;
+                if !members.insert(event.name.as_str()) {
+                    return Err(schema_error(format!("{} is declared twice", context)));
+                }
+                check_type(&event.ty, &context)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn schema_error(message: String) -> Error {
+    Error::Schema(message)
+}
+
+fn check_type_name(name: &str) -> Result<(), Error> {
+    let upper = match name.chars().next() {
+        Some(c) => c.is_ascii_uppercase(),
+        None => false,
+    };
+    if !upper || !is_identifier(name) {
+        return Err(schema_error(format!(
+            "{} has to be an upper camel case name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn check_member_name(name: &str) -> Result<(), Error> {
+    let lower = match name.chars().next() {
+        Some(c) => c.is_ascii_lowercase(),
+        None => false,
+    };
+    if !lower || !is_identifier(name) {
+        return Err(schema_error(format!(
+            "{} has to be a lower camel case name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => chars.all(|c| c.is_ascii_alphanumeric()),
+        _ => false,
+    }
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "try", "typeof", "unsized", "virtual", "yield",
+];
+
+pub(crate) fn is_rust_keyword(name: &str) -> bool {
+    RUST_KEYWORDS.contains(&name)
+}
+
+/// `isMaximized` to `is_maximized`
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !snake.is_empty() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// `is_maximized` to `isMaximized`, the way `flutter-engine-derive` names arguments
+pub(crate) fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_').filter(|part| !part.is_empty());
+    let mut camel = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        camel.push_str(&upper_camel_case(part));
+    }
+    camel
+}
+
+/// `moved` to `Moved`
+pub(crate) fn upper_camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Writes indented lines of code.
+pub(crate) struct CodeWriter {
+    code: String,
+    indent: usize,
+    indent_width: usize,
+}
+
+impl CodeWriter {
+    pub(crate) fn new(indent_width: usize) -> Self {
+        Self {
+            code: String::new(),
+            indent: 0,
+            indent_width,
+        }
+    }
+
+    pub(crate) fn line<S: AsRef<str>>(&mut self, line: S) {
+        let line = line.as_ref();
+        if !line.is_empty() {
+            for _ in 0..self.indent * self.indent_width {
+                self.code.push(' ');
+            }
+            self.code.push_str(line);
+        }
+        self.code.push('\n');
+    }
+
+    /// Write `line` and indent the following lines.
+    pub(crate) fn open<S: AsRef<str>>(&mut self, line: S) {
+        self.line(line);
+        self.indent += 1;
+    }
+
+    /// Stop indenting and write `line`.
+    pub(crate) fn close<S: AsRef<str>>(&mut self, line: S) {
+        self.indent -= 1;
+        self.line(line);
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_type() {
+        assert_eq!("int".parse(), Ok(Type::Int));
+        assert_eq!(
+            "List<Map<String, Position?>>?".parse(),
+            Ok(Type::Nullable(Box::new(Type::List(Box::new(Type::Map(
+                Box::new(Type::Nullable(Box::new(Type::Class("Position".to_owned()))))
+            ))))))
+        );
+        assert!("Map<int, String>".parse::<Type>().is_err());
+        assert!("int??".parse::<Type>().is_err());
+        assert!("List<int".parse::<Type>().is_err());
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(snake_case("isMaximized"), "is_maximized");
+        assert_eq!(camel_case("is_maximized"), "isMaximized");
+        assert_eq!(upper_camel_case("moved"), "Moved");
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let schema = r#"
+            [[class]]
+            name = "Position"
+            fields = [{ name = "x", type = "double" }]
+
+            [[channel]]
+            name = "WindowApi"
+            channel = "flutter-rs/window"
+
+            [[channel.method]]
+            name = "setPosition"
+            args = [{ name = "position", type = "Position" }]
+        "#;
+        assert!(Schema::parse(schema).is_ok());
+        assert!(Schema::parse(&schema.replace("\"Position\" }]", "\"Size\" }]")).is_err());
+        assert!(Schema::parse(&schema.replace("\"setPosition\"", "\"set_position\"")).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Rust bindings: serde structs for the data classes, a `#[method_channel]` trait per channel
+//! (with a client if its methods are implemented in Dart) and typed sinks for its events. They
+//! need the `derive` feature of `flutter-engine` and `serde`.
+
+use super::{is_rust_keyword, snake_case, upper_camel_case};
+use super::{Channel, Class, CodeWriter, Codec, Event, Handler, Schema, Type};
+
+pub fn generate(schema: &Schema) -> String {
+    let mut w = CodeWriter::new(4);
+    w.line("// Generated by flutter-codegen, do not edit.");
+    for class in &schema.classes {
+        w.line("");
+        write_class(&mut w, class);
+    }
+    for channel in &schema.channels {
+        if !channel.methods.is_empty() {
+            w.line("");
+            write_trait(&mut w, channel);
+        }
+        for event in &channel.events {
+            w.line("");
+            write_event(&mut w, channel, event);
+        }
+    }
+    w.finish()
+}
+
+fn rust_type(ty: &Type) -> String {
+    match ty {
+        Type::Bool => "bool".to_owned(),
+        Type::Int => "i64".to_owned(),
+        Type::Double => "f64".to_owned(),
+        Type::String => "String".to_owned(),
+        Type::List(ty) => format!("Vec<{}>", rust_type(ty)),
+        Type::Map(ty) => format!("::std::collections::HashMap<String, {}>", rust_type(ty)),
+        Type::Class(name) => name.clone(),
+        Type::Nullable(ty) => format!("Option<{}>", rust_type(ty)),
+    }
+}
+
+fn rust_name(name: &str) -> String {
+    let name = snake_case(name);
+    if is_rust_keyword(&name) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
+
+fn codec(codec: Codec) -> &'static str {
+    match codec {
+        Codec::Standard => "::flutter_engine::codec::STANDARD_CODEC",
+        Codec::Json => "::flutter_engine::codec::JSON_CODEC",
+    }
+}
+
+fn write_class(w: &mut CodeWriter, class: &Class) {
+    w.line("#[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]");
+    w.open(format!("pub struct {} {{", class.name));
+    for field in &class.fields {
+        // serde strips the `r#` of raw identifiers
+        if snake_case(&field.name) != field.name {
+            w.line(format!("#[serde(rename = \"{}\")]", field.name));
+        }
+        w.line(format!(
+            "pub {}: {},",
+            rust_name(&field.name),
+            rust_type(&field.ty)
+        ));
+    }
+    w.close("}");
+}
+
+fn write_trait(w: &mut CodeWriter, channel: &Channel) {
+    w.line("#[::flutter_engine::method_channel(");
+    w.line(format!("    name = \"{}\",", channel.channel));
+    w.line(format!("    codec = \"{}\",", codec(channel.codec)));
+    if channel.handler == Handler::Dart {
+        w.line("    client,");
+    }
+    w.line(")]");
+    w.open(format!("pub trait {} {{", channel.name));
+    for (i, method) in channel.methods.iter().enumerate() {
+        if i > 0 {
+            w.line("");
+        }
+        let args: Vec<String> = method
+            .args
+            .iter()
+            .map(|arg| format!(", {}: {}", rust_name(&arg.name), rust_type(&arg.ty)))
+            .collect();
+        let returns = match &method.returns {
+            Some(ty) => format!(" -> {}", rust_type(ty)),
+            None => String::new(),
+        };
+        w.line(format!("#[method(name = \"{}\")]", method.name));
+        w.line(format!(
+            "fn {}(&mut self{}){};",
+            rust_name(&method.name),
+            args.concat(),
+            returns
+        ));
+    }
+    w.close("}");
+}
+
+const EVENT_HANDLE: &str =
+    "::flutter_engine::completion::CompletionHandle<(), ::flutter_engine::error::EventError>";
+
+/// A handler trait for the listeners of the event, and a sink only accepting its type.
+fn write_event(w: &mut CodeWriter, channel: &Channel, event: &Event) {
+    let prefix = format!("{}{}", channel.name, upper_camel_case(&event.name));
+    let handler = format!("{}Handler", prefix);
+    let sink = format!("{}Sink", prefix);
+    let ty = rust_type(&event.ty);
+
+    w.line(format!(
+        "/// Handles the listeners of the `{}` events of `{}`.",
+        event.name, channel.name
+    ));
+    w.open(format!("pub trait {} {{", handler));
+    w.line(format!("fn on_listen(&mut self, sink: {});", sink));
+    w.line("");
+    w.line(format!("fn on_cancel(&mut self, _sink: {}) {{}}", sink));
+    w.close("}");
+    w.line("");
+
+    w.line("#[derive(Clone)]");
+    w.line(format!(
+        "pub struct {}(::flutter_engine::channel::EventSink);",
+        sink
+    ));
+    w.line("");
+    w.open(format!("impl {} {{", sink));
+    w.line(format!(
+        "pub const NAME: &'static str = \"{}\";",
+        channel.event_channel(event)
+    ));
+    w.line("");
+    w.line("/// Create the event channel, whose listeners are handled by `handler`.");
+    w.line("pub fn channel<H>(handler: H) -> ::flutter_engine::channel::EventChannel");
+    w.line("where");
+    w.line(format!("    H: {} + 'static,", handler));
+    w.open("{");
+    w.line("type Value = ::flutter_engine::codec::Value;");
+    w.line("type MethodError = ::flutter_engine::channel::MethodError<Value>;");
+    w.line("");
+    w.line("struct Adapter<H>(H);");
+    w.line("");
+    w.line("impl<H> ::flutter_engine::channel::EventHandler for Adapter<H>");
+    w.line("where");
+    w.line(format!("    H: {},", handler));
+    w.open("{");
+    w.line("fn on_listen(");
+    w.line("    &mut self,");
+    w.line("    _args: Value,");
+    w.line("    sink: ::flutter_engine::channel::EventSink,");
+    w.open(") -> Result<(), MethodError> {");
+    w.line(format!("self.0.on_listen({}(sink));", sink));
+    w.line("Ok(())");
+    w.close("}");
+    w.line("");
+    w.line("fn on_cancel(");
+    w.line("    &mut self,");
+    w.line("    _args: Value,");
+    w.line("    sink: ::flutter_engine::channel::EventSink,");
+    w.open(") -> Result<(), MethodError> {");
+    w.line(format!("self.0.on_cancel({}(sink));", sink));
+    w.line("Ok(())");
+    w.close("}");
+    w.close("}");
+    w.line("");
+    w.line(format!(
+        "::flutter_engine::channel::EventChannel::new(Self::NAME, Adapter(handler), &{})",
+        codec(channel.codec)
+    ));
+    w.close("}");
+    w.line("");
+    w.open("pub fn listen_id(&self) -> u64 {");
+    w.line("self.0.listen_id()");
+    w.close("}");
+    w.line("");
+    w.open("pub fn is_active(&self) -> bool {");
+    w.line("self.0.is_active()");
+    w.close("}");
+    w.line("");
+    w.open(format!(
+        "pub fn success(&self, event: &{}) -> {} {{",
+        ty, EVENT_HANDLE
+    ));
+    w.line("self.0.success(event)");
+    w.close("}");
+    w.line("");
+    w.open(format!(
+        "pub fn end_of_stream(&self) -> {} {{",
+        EVENT_HANDLE
+    ));
+    w.line("self.0.end_of_stream()");
+    w.close("}");
+    w.close("}");
+}
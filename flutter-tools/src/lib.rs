@@ -1,3 +1,5 @@
+pub mod codegen;
+
 use curl::easy::Easy;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::fs::File;
@@ -0,0 +1,76 @@
+//! Compares the bindings generated for `codegen/schema.toml` with `codegen/api.rs` and
+//! `codegen/api.dart`, and compiles the Rust bindings. After changing the generator, update them
+//! with `cargo run --bin flutter-codegen -- tests/codegen/schema.toml --rust tests/codegen/api.rs
+//! --dart tests/codegen/api.dart`.
+
+use flutter_engine::channel::Channel;
+use flutter_engine::codec::value::to_value;
+use flutter_engine::codec::Value;
+use flutter_engine::error::{EngineError, InvokeError};
+use flutter_tools::codegen::{dart, rust, Schema};
+use std::sync::Weak;
+
+#[allow(dead_code)]
+mod api {
+    include!("codegen/api.rs");
+}
+
+const SCHEMA: &str = include_str!("codegen/schema.toml");
+
+#[test]
+fn test_generate_rust() {
+    let schema = Schema::parse(SCHEMA).unwrap();
+    assert_eq!(rust::generate(&schema), include_str!("codegen/api.rs"));
+}
+
+#[test]
+fn test_generate_dart() {
+    let schema = Schema::parse(SCHEMA).unwrap();
+    assert_eq!(dart::generate(&schema), include_str!("codegen/api.dart"));
+}
+
+struct Window;
+
+impl api::WindowApi for Window {
+    fn set_position(&mut self, _position: api::Position) {}
+
+    fn resize(&mut self, _width: i64, _keep_aspect_ratio: Option<bool>) {}
+
+    fn get_state(&mut self) -> api::WindowState {
+        api::WindowState {
+            title: "flutter-rs".to_owned(),
+            position: None,
+            monitor_names: None,
+        }
+    }
+}
+
+impl api::WindowApiMovedHandler for Window {
+    fn on_listen(&mut self, _sink: api::WindowApiMovedSink) {}
+}
+
+#[test]
+fn test_rust_bindings() {
+    let channel = api::WindowApiChannel::channel(Window);
+    assert_eq!(channel.name(), "flutter-rs/window");
+    let channel = api::WindowApiMovedSink::channel(Window);
+    assert_eq!(channel.name(), "flutter-rs/window/moved");
+    assert_eq!(api::WindowApiFocusedSink::NAME, "flutter-rs/window-focus");
+
+    let state = api::WindowState {
+        title: "flutter-rs".to_owned(),
+        position: None,
+        monitor_names: Some(vec!["eDP-1".to_owned()]),
+    };
+    match to_value(&state).unwrap() {
+        Value::Map(map) => assert!(map.contains_key("monitorNames")),
+        value => panic!("expected a map, got {:?}", value),
+    }
+
+    let client = api::DialogApiClient::new(Weak::new());
+    let result = client.confirm("Quit?".to_owned(), None).wait();
+    assert!(matches!(
+        result,
+        Err(InvokeError::Engine(EngineError::EngineDropped))
+    ));
+}
@@ -0,0 +1,111 @@
+// Generated by flutter-codegen, do not edit.
+
+import 'package:flutter/services.dart';
+
+class Position {
+  Position({
+    required this.x,
+    required this.y,
+  });
+
+  final double x;
+  final double y;
+
+  Object encode() {
+    return <String, Object?>{
+      'x': x,
+      'y': y,
+    };
+  }
+
+  static Position decode(Object? message) {
+    final map = message as Map<Object?, Object?>;
+    return Position(
+      x: (map['x'] as num).toDouble(),
+      y: (map['y'] as num).toDouble(),
+    );
+  }
+}
+
+class WindowState {
+  WindowState({
+    required this.title,
+    this.position,
+    this.monitorNames,
+  });
+
+  final String title;
+  final Position? position;
+  final List<String>? monitorNames;
+
+  Object encode() {
+    return <String, Object?>{
+      'title': title,
+      'position': position?.encode(),
+      'monitorNames': monitorNames,
+    };
+  }
+
+  static WindowState decode(Object? message) {
+    final map = message as Map<Object?, Object?>;
+    return WindowState(
+      title: map['title'] as String,
+      position: map['position'] == null ? null : Position.decode(map['position']),
+      monitorNames: map['monitorNames'] == null ? null : (map['monitorNames'] as List<Object?>).map((e) => e as String).toList(),
+    );
+  }
+}
+
+class WindowApi {
+  static const MethodChannel _channel = MethodChannel('flutter-rs/window', JSONMethodCodec());
+
+  Future<void> setPosition(Position position) async {
+    await _channel.invokeMethod<Object?>('setPosition', position.encode());
+  }
+
+  Future<void> resize(int width, bool? keepAspectRatio) async {
+    await _channel.invokeMethod<Object?>('resize', <String, Object?>{'width': width, 'keepAspectRatio': keepAspectRatio});
+  }
+
+  Future<WindowState> getState() async {
+    final result = await _channel.invokeMethod<Object?>('getState');
+    return WindowState.decode(result);
+  }
+
+  static Stream<Position> get moved {
+    const channel = EventChannel('flutter-rs/window/moved', JSONMethodCodec());
+    return channel.receiveBroadcastStream().map((event) => Position.decode(event));
+  }
+
+  static Stream<bool> get focused {
+    const channel = EventChannel('flutter-rs/window-focus', JSONMethodCodec());
+    return channel.receiveBroadcastStream().map((event) => event as bool);
+  }
+}
+
+abstract class DialogApi {
+  static const MethodChannel _channel = MethodChannel('flutter-rs/dialog', StandardMethodCodec());
+
+  Future<bool> confirm(String title, String? detailText);
+
+  Future<void> close();
+
+  static void setup(DialogApi? api) {
+    if (api == null) {
+      _channel.setMethodCallHandler(null);
+      return;
+    }
+    _channel.setMethodCallHandler((MethodCall call) async {
+      if (call.method == 'confirm') {
+        final args = call.arguments as Map<Object?, Object?>;
+        final result = await api.confirm(args['title'] as String, args['detailText'] == null ? null : args['detailText'] as String);
+        return result;
+      }
+      if (call.method == 'close') {
+        await api.close();
+        return null;
+      }
+      throw MissingPluginException();
+    });
+  }
+}
@@ -0,0 +1,175 @@
+// Generated by flutter-codegen, do not edit.
+
+#[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+pub struct WindowState {
+    pub title: String,
+    pub position: Option<Position>,
+    #[serde(rename = "monitorNames")]
+    pub monitor_names: Option<Vec<String>>,
+}
+
+#[::flutter_engine::method_channel(
+    name = "flutter-rs/window",
+    codec = "::flutter_engine::codec::JSON_CODEC",
+)]
+pub trait WindowApi {
+    #[method(name = "setPosition")]
+    fn set_position(&mut self, position: Position);
+
+    #[method(name = "resize")]
+    fn resize(&mut self, width: i64, keep_aspect_ratio: Option<bool>);
+
+    #[method(name = "getState")]
+    fn get_state(&mut self) -> WindowState;
+}
+
+/// Handles the listeners of the `moved` events of `WindowApi`.
+pub trait WindowApiMovedHandler {
+    fn on_listen(&mut self, sink: WindowApiMovedSink);
+
+    fn on_cancel(&mut self, _sink: WindowApiMovedSink) {}
+}
+
+#[derive(Clone)]
+pub struct WindowApiMovedSink(::flutter_engine::channel::EventSink);
+
+impl WindowApiMovedSink {
+    pub const NAME: &'static str = "flutter-rs/window/moved";
+
+    /// Create the event channel, whose listeners are handled by `handler`.
+    pub fn channel<H>(handler: H) -> ::flutter_engine::channel::EventChannel
+    where
+        H: WindowApiMovedHandler + 'static,
+    {
+        type Value = ::flutter_engine::codec::Value;
+        type MethodError = ::flutter_engine::channel::MethodError<Value>;
+
+        struct Adapter<H>(H);
+
+        impl<H> ::flutter_engine::channel::EventHandler for Adapter<H>
+        where
+            H: WindowApiMovedHandler,
+        {
+            fn on_listen(
+                &mut self,
+                _args: Value,
+                sink: ::flutter_engine::channel::EventSink,
+            ) -> Result<(), MethodError> {
+                self.0.on_listen(WindowApiMovedSink(sink));
+                Ok(())
+            }
+
+            fn on_cancel(
+                &mut self,
+                _args: Value,
+                sink: ::flutter_engine::channel::EventSink,
+            ) -> Result<(), MethodError> {
+                self.0.on_cancel(WindowApiMovedSink(sink));
+                Ok(())
+            }
+        }
+
+        ::flutter_engine::channel::EventChannel::new(Self::NAME, Adapter(handler), &::flutter_engine::codec::JSON_CODEC)
+    }
+
+    pub fn listen_id(&self) -> u64 {
+        self.0.listen_id()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    pub fn success(&self, event: &Position) -> ::flutter_engine::completion::CompletionHandle<(), ::flutter_engine::error::EventError> {
+        self.0.success(event)
+    }
+
+    pub fn end_of_stream(&self) -> ::flutter_engine::completion::CompletionHandle<(), ::flutter_engine::error::EventError> {
+        self.0.end_of_stream()
+    }
+}
+
+/// Handles the listeners of the `focused` events of `WindowApi`.
+pub trait WindowApiFocusedHandler {
+    fn on_listen(&mut self, sink: WindowApiFocusedSink);
+
+    fn on_cancel(&mut self, _sink: WindowApiFocusedSink) {}
+}
+
+#[derive(Clone)]
+pub struct WindowApiFocusedSink(::flutter_engine::channel::EventSink);
+
+impl WindowApiFocusedSink {
+    pub const NAME: &'static str = "flutter-rs/window-focus";
+
+    /// Create the event channel, whose listeners are handled by `handler`.
+    pub fn channel<H>(handler: H) -> ::flutter_engine::channel::EventChannel
+    where
+        H: WindowApiFocusedHandler + 'static,
+    {
+        type Value = ::flutter_engine::codec::Value;
+        type MethodError = ::flutter_engine::channel::MethodError<Value>;
+
+        struct Adapter<H>(H);
+
+        impl<H> ::flutter_engine::channel::EventHandler for Adapter<H>
+        where
+            H: WindowApiFocusedHandler,
+        {
+            fn on_listen(
+                &mut self,
+                _args: Value,
+                sink: ::flutter_engine::channel::EventSink,
+            ) -> Result<(), MethodError> {
+                self.0.on_listen(WindowApiFocusedSink(sink));
+                Ok(())
+            }
+
+            fn on_cancel(
+                &mut self,
+                _args: Value,
+                sink: ::flutter_engine::channel::EventSink,
+            ) -> Result<(), MethodError> {
+                self.0.on_cancel(WindowApiFocusedSink(sink));
+                Ok(())
+            }
+        }
+
+        ::flutter_engine::channel::EventChannel::new(Self::NAME, Adapter(handler), &::flutter_engine::codec::JSON_CODEC)
+    }
+
+    pub fn listen_id(&self) -> u64 {
+        self.0.listen_id()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    pub fn success(&self, event: &bool) -> ::flutter_engine::completion::CompletionHandle<(), ::flutter_engine::error::EventError> {
+        self.0.success(event)
+    }
+
+    pub fn end_of_stream(&self) -> ::flutter_engine::completion::CompletionHandle<(), ::flutter_engine::error::EventError> {
+        self.0.end_of_stream()
+    }
+}
+
+#[::flutter_engine::method_channel(
+    name = "flutter-rs/dialog",
+    codec = "::flutter_engine::codec::STANDARD_CODEC",
+    client,
+)]
+pub trait DialogApi {
+    #[method(name = "confirm")]
+    fn confirm(&mut self, title: String, detail_text: Option<String>) -> bool;
+
+    #[method(name = "close")]
+    fn close(&mut self);
+}